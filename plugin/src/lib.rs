@@ -0,0 +1,277 @@
+// the resonator of the demo as a stereo clap/vst3 effect. switch learn on while playing the audio the
+// resonators should be tuned to and off again, the plan is made from what was captured in between and
+// saved with the plugin state. until then the input is passed through dry. the plugin is its own
+// workspace, from this directory check a bundle with e.g.
+//     clap-validator validate target/bundled/capstone_resonator_plugin.clap
+//     pluginval --strictness-level 5 --validate target/bundled/capstone_resonator_plugin.vst3
+use nih_plug::prelude::*;
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::f64::consts::PI;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use capstone_audio_demo::state::AudioState;
+use params::{ResonatorParams, SavedPlan};
+
+mod params;
+
+// longest capture the planner gets in seconds, the rest is ignored
+const MAX_LEARN_SECONDS: f64 = 30.0;
+
+pub enum Task {
+    // runs the planner on the captured left and right channel, recorded at the sample rate
+    Plan([Vec<f64>; 2], f64),
+    // builds the resonators of the saved plan again at the sample rate
+    Build(f64),
+}
+
+// what the background task hands to the audio thread. process only ever try_locks these, so it
+// neither waits on the planner nor allocates or frees anything
+#[derive(Default)]
+struct Handover {
+    // a track with newly built resonators, or the one process swapped out for it, which is then
+    // dropped here on the next build
+    state: Mutex<Option<AudioState>>,
+    // state holds a track process hasn't taken yet
+    ready: AtomicBool,
+    // the capture buffers, emptied after planning, for the next time learn is switched on
+    buffers: Mutex<Option<[Vec<f64>; 2]>>,
+}
+
+impl Handover {
+    fn hand_over(&self, audio_state: AudioState) {
+        *self.state.lock().unwrap() = Some(audio_state);
+        self.ready.store(true, Ordering::Release);
+    }
+}
+
+pub struct ResonatorPlugin {
+    params: Arc<ResonatorParams>,
+    // a track without a file, the input goes through its resonators like the source file in the demo
+    audio_state: AudioState,
+    handover: Arc<Handover>,
+    // set by reset, process asks the background task for fresh resonators
+    rebuild: bool,
+    learning: bool,
+    learned: [Vec<f64>; 2],
+    // the buffers the next capture goes into while the planner has the last one
+    spare: Option<[Vec<f64>; 2]>,
+    // interleaved scratch buffers for add_audio_with_input
+    input: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl Default for ResonatorPlugin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(ResonatorParams::default()),
+            audio_state: AudioState::for_input(44100.0),
+            handover: Arc::new(Handover::default()),
+            rebuild: false,
+            learning: false,
+            learned: [Vec::new(), Vec::new()],
+            spare: None,
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl ResonatorPlugin {
+    // swaps in the track the background task built, if there's one and the handover isn't busy
+    #[inline]
+    fn take_built(&mut self) {
+        if !self.handover.ready.load(Ordering::Acquire) {
+            return;
+        }
+        if let Ok(mut slot) = self.handover.state.try_lock() {
+            if let Some(built) = slot.as_mut() {
+                // one built before the host changed the sample rate is left to be dropped
+                if built.sample_rate == self.audio_state.sample_rate {
+                    std::mem::swap(&mut self.audio_state, built);
+                }
+            }
+            self.handover.ready.store(false, Ordering::Release);
+        }
+    }
+
+    // takes back the capture buffers the planner is done with
+    #[inline]
+    fn take_buffers(&mut self) {
+        if self.spare.is_some() {
+            return;
+        }
+        if let Ok(mut buffers) = self.handover.buffers.try_lock() {
+            self.spare = buffers.take();
+        }
+    }
+}
+
+// a track with the resonators of the saved plan at the sample rate, without any when there's none.
+// allocates, so it runs on initialize and the background task
+fn build_state(params: &ResonatorParams, sample_rate: f64) -> AudioState {
+    let mut audio_state = AudioState::for_input(sample_rate);
+    let saved = params.plan.read().unwrap().clone();
+    let saved = match saved {
+        Some(v) => v,
+        None => return audio_state,
+    };
+    let plans = (
+        resample_plan(&saved.left, saved.sample_rate, sample_rate),
+        resample_plan(&saved.right, saved.sample_rate, sample_rate),
+    );
+    if let Err(e) = audio_state.set_plan(plans, None) {
+        nih_log!("Error occurred while building the resonators: {:?}", e);
+        return audio_state;
+    }
+    // apply the decay and transpose to the new arrays on the first buffer
+    audio_state.old_decay = f64::NAN;
+    audio_state.old_transpose = f64::NAN;
+    audio_state
+}
+
+// a plan with args relative to another sample rate, leaving out resonators above the nyquist frequency
+fn resample_plan(resonators: &[(f64, f64)], from: f64, to: f64) -> ScaledResonatorPlan {
+    let mut plan = ScaledResonatorPlan::empty();
+    plan.resonators = resonators
+        .iter()
+        .map(|(arg, amp)| (arg * from / to, *amp))
+        .filter(|(arg, _)| *arg < PI)
+        .collect();
+    plan
+}
+
+impl Plugin for ResonatorPlugin {
+    const NAME: &'static str = "Capstone Resonator";
+    const VENDOR: &'static str = "CapstoneAudioDemo";
+    const URL: &'static str = "";
+    const EMAIL: &'static str = "";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+        ..AudioIOLayout::const_default()
+    }];
+
+    type SysExMessage = ();
+    type BackgroundTask = Task;
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = Arc::clone(&self.params);
+        let handover = Arc::clone(&self.handover);
+        Box::new(move |task| match task {
+            Task::Plan(mut channels, sample_rate) => {
+                match params.planner.settings().plan_channels(&channels) {
+                    Ok((left, right)) => {
+                        nih_log!("Learned {} + {} resonators", left.resonators.len(), right.resonators.len());
+                        *params.plan.write().unwrap() = Some(SavedPlan {
+                            left: left.resonators,
+                            right: right.resonators,
+                            sample_rate,
+                        });
+                        handover.hand_over(build_state(&params, sample_rate));
+                    },
+                    Err(e) => nih_log!("Error occurred while planning the learned audio: {:?}", e),
+                }
+                channels.iter_mut().for_each(Vec::clear);
+                *handover.buffers.lock().unwrap() = Some(channels);
+            },
+            Task::Build(sample_rate) => handover.hand_over(build_state(&params, sample_rate)),
+        })
+    }
+
+    fn initialize(&mut self, _audio_io_layout: &AudioIOLayout, buffer_config: &BufferConfig, _context: &mut impl InitContext<Self>) -> bool {
+        let sample_rate = buffer_config.sample_rate as f64;
+        // also picks up a plan restored with the plugin state
+        self.audio_state = build_state(&self.params, sample_rate);
+        let buffer_len = 2 * buffer_config.max_buffer_size as usize;
+        self.input = vec![0.0; buffer_len];
+        self.output = vec![0.0; buffer_len];
+        let capacity = (MAX_LEARN_SECONDS * sample_rate) as usize;
+        self.learned = [Vec::with_capacity(capacity), Vec::with_capacity(capacity)];
+        self.spare = Some([Vec::with_capacity(capacity), Vec::with_capacity(capacity)]);
+        // anything built for the last sample rate is stale
+        *self.handover.state.lock().unwrap() = None;
+        self.handover.ready.store(false, Ordering::Release);
+        *self.handover.buffers.lock().unwrap() = None;
+        true
+    }
+
+    fn reset(&mut self) {
+        // fresh resonators so nothing rings on from before, built off the audio thread
+        self.audio_state.limiter_scale = 0.0;
+        self.rebuild = true;
+    }
+
+    fn process(&mut self, buffer: &mut Buffer, _aux: &mut AuxiliaryBuffers, context: &mut impl ProcessContext<Self>) -> ProcessStatus {
+        if self.rebuild {
+            self.rebuild = false;
+            context.execute_background(Task::Build(self.audio_state.sample_rate));
+        }
+        self.take_built();
+        self.take_buffers();
+        let frames = buffer.samples();
+        let channels = buffer.as_slice();
+
+        let learn = self.params.learn.value();
+        if learn {
+            if !self.learning {
+                self.learned.iter_mut().for_each(Vec::clear);
+            }
+            let room = self.learned[0].capacity() - self.learned[0].len();
+            for (learned, channel) in self.learned.iter_mut().zip(channels.iter()) {
+                learned.extend(channel[..frames.min(room)].iter().map(|v| *v as f64));
+            }
+        } else if self.learning && !self.learned[0].is_empty() {
+            // the last capture is still being planned when there's no spare, this one is dropped
+            if let Some(spare) = self.spare.take() {
+                let learned = std::mem::replace(&mut self.learned, spare);
+                context.execute_background(Task::Plan(learned, self.audio_state.sample_rate));
+            }
+        }
+        self.learning = learn;
+
+        let audio_state = &mut self.audio_state;
+        audio_state.params.decay = self.params.decay.value() as f64;
+        audio_state.params.transpose = self.params.transpose.value() as f64;
+        audio_state.params.volume = self.params.volume.value() as f64;
+
+        let input = &mut self.input[..2 * frames];
+        let output = &mut self.output[..2 * frames];
+        for i in 0..frames {
+            input[2 * i] = channels[0][i];
+            input[2 * i + 1] = channels[1][i];
+        }
+        output.iter_mut().for_each(|v| *v = 0.0);
+        audio_state.add_audio_with_input(output, Some(input));
+        for i in 0..frames {
+            channels[0][i] = output[2 * i];
+            channels[1][i] = output[2 * i + 1];
+        }
+
+        // let the host keep calling while the resonators ring out
+        let ring_time = 4_f64.powf(audio_state.params.decay) - 1.0;
+        ProcessStatus::Tail((ring_time * audio_state.sample_rate) as u32)
+    }
+}
+
+impl ClapPlugin for ResonatorPlugin {
+    const CLAP_ID: &'static str = "com.capstone-audio-demo.resonator";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Resonators tuned to the spectrum of learned audio");
+    const CLAP_MANUAL_URL: Option<&'static str> = None;
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::AudioEffect, ClapFeature::Stereo, ClapFeature::Filter];
+}
+
+impl Vst3Plugin for ResonatorPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CapstoneResonatr";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Fx, Vst3SubCategory::Filter];
+}
+
+nih_export_clap!(ResonatorPlugin);
+nih_export_vst3!(ResonatorPlugin);
@@ -0,0 +1,103 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use capstone_audio_demo::planner::PlannerSettings;
+use capstone_audio_demo::state::DEFAULT_DECAY;
+
+// (arg, amplitude) of every resonator of each channel, like the plan in a session file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedPlan {
+    pub left: Vec<(f64, f64)>,
+    pub right: Vec<(f64, f64)>,
+    // the sample rate the args are relative to, the host may run at another one when the state is loaded
+    pub sample_rate: f64,
+}
+
+#[derive(Params)]
+pub struct ResonatorParams {
+    // the last plan learned from the input, saved with the plugin state
+    #[persist = "plan"]
+    pub plan: Arc<RwLock<Option<SavedPlan>>>,
+
+    #[id = "decay"]
+    pub decay: FloatParam,
+    #[id = "transpose"]
+    pub transpose: FloatParam,
+    #[id = "volume"]
+    pub volume: FloatParam,
+    // captures the input while on, the planner runs on the capture when it's switched off
+    #[id = "learn"]
+    pub learn: BoolParam,
+
+    #[nested(group = "Planner")]
+    pub planner: PlannerParams,
+}
+
+// the sliders next to the graph in the demo, used the next time a plan is learned
+#[derive(Params)]
+pub struct PlannerParams {
+    #[id = "stereo"]
+    pub stereo: BoolParam,
+    #[id = "min_line"]
+    pub min_line: FloatParam,
+    #[id = "min_range"]
+    pub min_range: FloatParam,
+    #[id = "max_range"]
+    pub max_range: FloatParam,
+    #[id = "min_prominence"]
+    pub min_prominence: FloatParam,
+    #[id = "max_peaks"]
+    pub max_peaks: FloatParam,
+}
+
+impl Default for ResonatorParams {
+    fn default() -> Self {
+        Self {
+            plan: Arc::new(RwLock::new(None)),
+            decay: FloatParam::new("Decay", DEFAULT_DECAY as f32, FloatRange::Linear { min: 0.0, max: 1.0 })
+                // shown as the ring time, 4^decay - 1 seconds
+                .with_value_to_string(Arc::new(|v| format!("{:.2} s", 4_f32.powf(v) - 1.0))),
+            transpose: FloatParam::new("Transpose", 0.0, FloatRange::Linear { min: -1.0, max: 1.0 })
+                .with_unit(" oct")
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            volume: FloatParam::new("Volume", 0.0, FloatRange::Linear { min: -40.0, max: 6.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            learn: BoolParam::new("Learn", false),
+            planner: PlannerParams::default(),
+        }
+    }
+}
+
+impl Default for PlannerParams {
+    fn default() -> Self {
+        let settings = PlannerSettings::default();
+        let slider = |name: &str, value: f64| {
+            FloatParam::new(name, value as f32, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(3))
+        };
+        Self {
+            stereo: BoolParam::new("Stereo", settings.stereo),
+            min_line: slider("Min Line", settings.min_line),
+            min_range: slider("Min Freq", settings.min_range),
+            max_range: slider("Max Freq", settings.max_range),
+            min_prominence: slider("Min Prominence", settings.min_prominence),
+            max_peaks: slider("Max Peaks", settings.max_peaks),
+        }
+    }
+}
+
+impl PlannerParams {
+    // the whole capture is planned with the default analysis
+    pub fn settings(&self) -> PlannerSettings {
+        PlannerSettings {
+            stereo: self.stereo.value(),
+            min_line: self.min_line.value() as f64,
+            min_range: self.min_range.value() as f64,
+            max_range: self.max_range.value() as f64,
+            min_prominence: self.min_prominence.value() as f64,
+            max_peaks: self.max_peaks.value() as f64,
+            ..PlannerSettings::default()
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::error::Error;
+use resonator_builder::fft::FftCalculator;
+use resonator_builder::fft::window::{Rectangular, WindowFunction};
+use serde::{Deserialize, Serialize};
+
+// lowest log10 magnitude displayed in the spectrum and spectrogram
+pub const MIN_LOG_VALUE: f64 = -3.0;
+
+pub const SPECTROGRAM_FRAME_SIZE: usize = 2048;
+pub const SPECTROGRAM_COLUMNS: usize = 256;
+pub const SPECTROGRAM_ROWS: usize = 128;
+
+const KAISER_BETA: f64 = 8.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AnalysisWindow {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+    Kaiser,
+    FlatTop,
+}
+
+impl AnalysisWindow {
+    pub const ALL: [AnalysisWindow; 7] = [
+        AnalysisWindow::Rectangular,
+        AnalysisWindow::Hann,
+        AnalysisWindow::Hamming,
+        AnalysisWindow::Blackman,
+        AnalysisWindow::BlackmanHarris,
+        AnalysisWindow::Kaiser,
+        AnalysisWindow::FlatTop,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnalysisWindow::Rectangular => "rectangular",
+            AnalysisWindow::Hann => "hann",
+            AnalysisWindow::Hamming => "hamming",
+            AnalysisWindow::Blackman => "blackman",
+            AnalysisWindow::BlackmanHarris => "blackman-harris",
+            AnalysisWindow::Kaiser => "kaiser",
+            AnalysisWindow::FlatTop => "flat-top",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|w| w.name() == name.to_lowercase())
+    }
+
+    // the window after this one, used to cycle through windows in the ui
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|w| w == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn coefficients(&self, len: usize) -> Vec<f64> {
+        use std::f64::consts::PI;
+        let n = (len.max(2) - 1) as f64;
+        let cosine_sum = |a: &[f64], i: usize| {
+            let x = 2.0 * PI * i as f64 / n;
+            a.iter()
+                .enumerate()
+                .map(|(k, a_k)| {
+                    let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                    sign * a_k * (k as f64 * x).cos()
+                })
+                .sum::<f64>()
+        };
+        (0..len)
+            .map(|i| match self {
+                AnalysisWindow::Rectangular => 1.0,
+                AnalysisWindow::Hann => cosine_sum(&[0.5, 0.5], i),
+                AnalysisWindow::Hamming => cosine_sum(&[0.54, 0.46], i),
+                AnalysisWindow::Blackman => cosine_sum(&[0.42, 0.5, 0.08], i),
+                AnalysisWindow::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168], i),
+                AnalysisWindow::FlatTop => cosine_sum(&[0.21557895, 0.41663158, 0.277263158, 0.083578947, 0.006947368], i),
+                AnalysisWindow::Kaiser => {
+                    let r = 2.0 * i as f64 / n - 1.0;
+                    bessel_i0(KAISER_BETA * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(KAISER_BETA)
+                },
+            })
+            .collect()
+    }
+
+    // multiply `audio` by this window
+    #[inline]
+    pub fn apply(&self, audio: &[f64]) -> Vec<f64> {
+        if *self == AnalysisWindow::Rectangular {
+            return audio.to_vec();
+        }
+        audio.iter()
+            .zip(self.coefficients(audio.len()))
+            .map(|(v, w)| v * w)
+            .collect()
+    }
+}
+
+// zeroth order modified bessel function of the first kind
+#[inline]
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+// how the resonant file is analyzed. the planner sees the same spectrum, so the graph shows what
+// the peaks are picked from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisSettings {
+    pub window: AnalysisWindow,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        Self {
+            window: AnalysisWindow::Rectangular,
+        }
+    }
+}
+
+// returns the normalized spectrum (values between 0.0 and 1.0) along with its scale and base
+#[inline]
+pub fn compute_spectrum(audio: &[f64], resolution: usize, settings: &AnalysisSettings) -> Result<(Vec<f64>, f64, f64), Box<dyn Error>> {
+    let (mut specs, scale, base) = compute_spectra(&[audio], resolution, settings)?;
+    Ok((specs.remove(0), scale, base))
+}
+
+// like compute_spectrum but normalizes every channel with the same scale so they can be overlaid
+pub fn compute_spectra(channels: &[&[f64]], resolution: usize, settings: &AnalysisSettings) -> Result<(Vec<Vec<f64>>, f64, f64), Box<dyn Error>> {
+    let mut spectra = Vec::with_capacity(channels.len());
+    for audio in channels {
+        spectra.push(single_fft(audio, settings)?);
+    }
+
+    let mut global_max = f64::MIN;
+    for v in spectra.iter().flatten() {
+        if *v > global_max {
+            global_max = *v;
+        }
+    }
+    let scale = global_max - MIN_LOG_VALUE;
+    let out = spectra.iter()
+        .map(|freqs| {
+            downsample_bins(freqs, resolution)
+                .into_iter()
+                .map(|v| (v.max(MIN_LOG_VALUE) - MIN_LOG_VALUE) / scale)
+                .collect()
+        })
+        .collect();
+    Ok((out, scale, MIN_LOG_VALUE))
+}
+
+// log10 magnitudes of one windowed fft over the whole of audio, zero padded to the next power of two
+fn single_fft(audio: &[f64], settings: &AnalysisSettings) -> Result<Vec<f64>, Box<dyn Error>> {
+    let near_pow_2 = (audio.len() - 1).ilog2() + 1;
+    let fft_size = 2_usize.pow(near_pow_2);
+    let frame = settings.window.apply(audio);
+    let mut fft = FftCalculator::new(frame.len(), fft_size - frame.len())?;
+    let comp_freqs = fft.real_fft(&frame[..], Rectangular::real_window);
+
+    Ok(
+        comp_freqs[0..fft_size / 2]
+            .into_iter()
+            .map(|v| v.norm().log10())
+            .collect()
+    )
+}
+
+// short time fourier transform of the whole file, one column per frame.
+// each column holds `SPECTROGRAM_ROWS` values between 0.0 and 1.0 from low to high frequency
+pub fn compute_spectrogram(audio: &[f64]) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+    let frame_size = SPECTROGRAM_FRAME_SIZE.min(2_usize.pow(audio.len().max(2).ilog2()));
+    let hop = if audio.len() > frame_size {
+        (audio.len() - frame_size) as f64 / (SPECTROGRAM_COLUMNS - 1) as f64
+    } else {
+        0.0
+    };
+    let window = AnalysisWindow::Hann.coefficients(frame_size);
+
+    let mut fft = FftCalculator::new(frame_size, 0)?;
+    let mut frame = vec![0.0; frame_size];
+    let mut columns = Vec::with_capacity(SPECTROGRAM_COLUMNS);
+    let mut global_max = f64::MIN;
+    for col in 0..SPECTROGRAM_COLUMNS {
+        let start = (col as f64 * hop) as usize;
+        for i in 0..frame_size {
+            frame[i] = audio.get(start + i).copied().unwrap_or(0.0) * window[i];
+        }
+        let comp_freqs = fft.real_fft(&frame[..], Rectangular::real_window);
+        let freqs = comp_freqs[0..frame_size / 2]
+            .into_iter()
+            .map(|v| v.norm().log10())
+            .collect::<Vec<f64>>();
+        let column = downsample_bins(&freqs, SPECTROGRAM_ROWS);
+        for v in &column {
+            if *v > global_max {
+                global_max = *v;
+            }
+        }
+        columns.push(column);
+    }
+
+    let scale = global_max - MIN_LOG_VALUE;
+    for column in columns.iter_mut() {
+        for v in column.iter_mut() {
+            *v = (v.max(MIN_LOG_VALUE) - MIN_LOG_VALUE) / scale;
+        }
+    }
+    Ok(columns)
+}
+
+// reduce `bins` to `resolution` values by taking the max of each group
+#[inline]
+fn downsample_bins(bins: &[f64], resolution: usize) -> Vec<f64> {
+    let mut cur_bin = 0;
+    let mut out = Vec::with_capacity(resolution);
+    for i in 0..resolution {
+        let mut max = f64::MIN;
+        while cur_bin < bins.len() && (cur_bin as f64 / bins.len() as f64) < ((i + 1) as f64 / resolution as f64) {
+            if max < bins[cur_bin] {
+                max = bins[cur_bin];
+            }
+            cur_bin += 1;
+        }
+        // more output values than bins: repeat the previous value
+        if max == f64::MIN {
+            max = out.last().copied().unwrap_or(MIN_LOG_VALUE);
+        }
+        out.push(max)
+    }
+    out
+}
@@ -0,0 +1,147 @@
+use druid::widget::prelude::*;
+use druid::{Color, Rect, TimerToken};
+use druid::kurbo::{BezPath, Point};
+use resonator_builder::fft::FftCalculator;
+use resonator_builder::fft::window::{Rectangular, WindowFunction};
+use rtrb::Consumer;
+use std::time::Duration;
+use crate::analysis::AnalysisWindow;
+use crate::graph::GraphData;
+
+const FFT_SIZE: usize = 4096;
+const REFRESH_INTERVAL: Duration = Duration::from_millis(33);
+// lowest level shown, in db relative to full scale
+const MIN_DB: f64 = -100.0;
+// how fast the displayed spectrum falls, per refresh
+const FALLOFF: f64 = 0.02;
+
+// a live spectrum of the mixed output, fed by stream::write_audio through a ring buffer.
+// the planned resonances are marked so it's easy to see whether the output lands on them
+pub struct OutputAnalyzer {
+    consumer: Consumer<f32>,
+    // the last FFT_SIZE samples, oldest first once full
+    history: Vec<f32>,
+    write_pos: usize,
+    fft: Option<FftCalculator>,
+    window: Vec<f64>,
+    window_gain: f64,
+    // displayed values between 0.0 and 1.0
+    spectrum: Vec<f64>,
+    output_sample_rate: f64,
+    timer: TimerToken,
+}
+
+impl OutputAnalyzer {
+    pub fn new(consumer: Consumer<f32>, output_sample_rate: f64) -> Self {
+        let window = AnalysisWindow::Hann.coefficients(FFT_SIZE);
+        let window_gain = window.iter().sum::<f64>();
+        let fft = match FftCalculator::new(FFT_SIZE, 0) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Error occurred while creating output analyzer: {:?}", e);
+                None
+            }
+        };
+        Self {
+            consumer,
+            history: vec![0.0; FFT_SIZE],
+            write_pos: 0,
+            fft,
+            window,
+            window_gain,
+            spectrum: vec![0.0; FFT_SIZE / 2],
+            output_sample_rate,
+            timer: TimerToken::INVALID,
+        }
+    }
+
+    fn refresh(&mut self) {
+        while let Ok(v) = self.consumer.pop() {
+            self.history[self.write_pos] = v;
+            self.write_pos = (self.write_pos + 1) % FFT_SIZE;
+        }
+        let fft = match self.fft.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        let frame = (0..FFT_SIZE)
+            .map(|i| self.history[(self.write_pos + i) % FFT_SIZE] as f64 * self.window[i])
+            .collect::<Vec<f64>>();
+        let comp_freqs = fft.real_fft(&frame[..], Rectangular::real_window);
+        for (v, c) in self.spectrum.iter_mut().zip(comp_freqs[0..FFT_SIZE / 2].iter()) {
+            // a full scale sine reads 0 db
+            let db = 20.0 * (2.0 * c.norm() / self.window_gain).max(1e-12).log10();
+            let new = ((db - MIN_DB) / -MIN_DB).max(0.0).min(1.0);
+            *v = new.max(*v - FALLOFF);
+        }
+    }
+}
+
+impl Widget<GraphData> for OutputAnalyzer {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut GraphData, _env: &Env) {
+        match event {
+            Event::WindowConnected => {
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+            },
+            Event::Timer(token) if *token == self.timer => {
+                self.refresh();
+                ctx.request_paint();
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+            },
+            _ => {},
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &GraphData, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &GraphData, data: &GraphData, _env: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &GraphData, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &GraphData, _env: &Env) {
+        let size = ctx.size();
+        let bg = Rect::new(0.0, 0.0, size.width, size.height);
+        ctx.fill(bg, &Color::rgb8(0, 0, 0));
+
+        // the x axis matches LineGraph: 0 to the nyquist frequency of the resonant file
+        let max_freq = data.sample_rate / 2.0;
+        let bin_freq = self.output_sample_rate / FFT_SIZE as f64;
+
+        // the plans cached by LineGraph, recomputing them here every frame would be too slow
+        let peaks = if data.planner.stereo {
+            let plans = data.channel_plans.lock();
+            plans.iter().flat_map(|p| p.resonators.iter().map(|r| r.0)).collect::<Vec<f64>>()
+        } else {
+            data.plan.lock().resonators.iter().map(|r| r.0).collect::<Vec<f64>>()
+        };
+        let grey = Color::rgba8(255, 255, 255, 48);
+        for arg in peaks {
+            let x = arg / std::f64::consts::PI * size.width;
+            let mut path = BezPath::new();
+            path.move_to(Point::new(x, 0.0));
+            path.line_to(Point::new(x, size.height));
+            ctx.stroke(path, &grey, 1.0);
+        }
+
+        let mut path = BezPath::new();
+        for (i, v) in self.spectrum.iter().enumerate() {
+            let freq = i as f64 * bin_freq;
+            if freq > max_freq {
+                break;
+            }
+            let point = Point::new(freq / max_freq * size.width, GraphData::value_to_pixel(size.height, *v));
+            if i == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        ctx.stroke(path, &Color::rgb8(0xe1, 0xb4, 0x1e), 1.5);
+    }
+}
@@ -0,0 +1,188 @@
+use std::env;
+use std::error::Error;
+use crate::analysis::{AnalysisSettings, AnalysisWindow};
+use crate::midi::MidiConfig;
+use crate::recorder::RecordFormat;
+use crate::batch::BatchConfig;
+
+const DEFAULT_AUDIO_PATH: &str = "./audio/poem1b.wav";
+const DEFAULT_R_AUDIO_PATH: &str = "./audio/Datmosphere.wav";
+const DEFAULT_JACK_NAME: &str = "capstone";
+
+// command line arguments:
+// [source file] [resonant file] [--window <name>] [--stereo]
+// [--midi] [--midi-port <name>] [--midi-root <note>] [--osc-port <port>]
+// [--record <path>] [--record-format <16|24|32f>] [--automation <path>] [--keys <path>]
+// [--session <path>] [--import <path|spec>]
+// [--batch <dir|glob>] [--batch-resonant <path>]... [--batch-out <template>] [--batch-report <path>] [--batch-tail <seconds>]
+// [--jack] [--jack-name <name>] [--modulation <path>] [--shaping <path>]
+pub struct Args {
+    pub audio_path: String,
+    pub r_audio_path: String,
+    pub analysis: AnalysisSettings,
+    // analyze the resonant file per channel
+    pub stereo: bool,
+    // play the resonator from midi when set
+    pub midi: Option<MidiConfig>,
+    // serve osc on this localhost udp port when set
+    pub osc_port: Option<u16>,
+    // record the output to this wav file from startup when set
+    pub record_path: Option<String>,
+    pub record_format: RecordFormat,
+    // automation file to save to, played back from startup when it exists
+    pub automation_path: Option<String>,
+    // key bindings file, see keys::KeyBindings
+    pub keys_path: String,
+    // session file to save to, restored at startup when it exists. overrides the paths and analysis settings
+    pub session_path: Option<String>,
+    // plan file or spec to use instead of analyzing the resonant file, see import::import_plan
+    pub import: Option<String>,
+    // render files without the ui when set
+    pub batch: Option<BatchConfig>,
+    // play through a jack client with this name instead of the default output device when set
+    pub jack: Option<String>,
+    // lfo and envelope routing for the source track, see modulation::ModulationSettings
+    pub modulation_path: Option<String>,
+    // tilts, curves and peak overrides for the source track's resonators, see shaping::ShapingSettings
+    pub shaping_path: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, Box<dyn Error>> {
+        Self::parse_from(env::args().skip(1))
+    }
+
+    pub fn parse_from<I: Iterator<Item = String>>(mut args: I) -> Result<Self, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        let mut analysis = AnalysisSettings::default();
+        let mut stereo = false;
+        let mut midi: Option<MidiConfig> = None;
+        let mut osc_port = None;
+        let mut record_path = None;
+        let mut record_format = RecordFormat::default();
+        let mut automation_path = None;
+        let mut keys_path = crate::keys::DEFAULT_KEYS_PATH.to_string();
+        let mut session_path = None;
+        let mut import = None;
+        let mut batch: Option<BatchConfig> = None;
+        let mut jack = None;
+        let mut modulation_path = None;
+        let mut shaping_path = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--window" => {
+                    let name = args.next().ok_or("--window expects a window name")?;
+                    analysis.window = AnalysisWindow::from_name(&name)
+                        .ok_or_else(|| format!("Unknown window '{}', expected one of: {}", name, window_names()))?;
+                },
+                "--stereo" => stereo = true,
+                "--midi" => {
+                    midi.get_or_insert_with(MidiConfig::default);
+                },
+                "--midi-port" => {
+                    let port = args.next().ok_or("--midi-port expects a port name")?;
+                    midi.get_or_insert_with(MidiConfig::default).port = Some(port);
+                },
+                "--midi-root" => {
+                    let note = args.next().ok_or("--midi-root expects a note number")?;
+                    midi.get_or_insert_with(MidiConfig::default).root_note = note.parse::<u8>()
+                        .ok()
+                        .filter(|n| *n < 128)
+                        .ok_or_else(|| format!("Invalid midi note '{}'", note))?;
+                },
+                "--osc-port" => {
+                    let port = args.next().ok_or("--osc-port expects a port")?;
+                    osc_port = Some(port.parse::<u16>().map_err(|e| format!("Invalid osc port '{}': {}", port, e))?);
+                },
+                "--record" => {
+                    record_path = Some(args.next().ok_or("--record expects a file path")?);
+                },
+                "--record-format" => {
+                    let name = args.next().ok_or("--record-format expects a format")?;
+                    record_format = RecordFormat::from_name(&name)
+                        .ok_or_else(|| format!("Unknown record format '{}', expected one of: 16, 24, 32f", name))?;
+                },
+                "--automation" => {
+                    automation_path = Some(args.next().ok_or("--automation expects a file path")?);
+                },
+                "--keys" => {
+                    keys_path = args.next().ok_or("--keys expects a file path")?;
+                },
+                "--session" => {
+                    session_path = Some(args.next().ok_or("--session expects a file path")?);
+                },
+                "--import" => {
+                    import = Some(args.next().ok_or("--import expects a plan file or spec")?);
+                },
+                "--batch" => {
+                    batch.get_or_insert_with(BatchConfig::default).sources = args.next().ok_or("--batch expects a directory or glob")?;
+                },
+                "--batch-resonant" => {
+                    let path = args.next().ok_or("--batch-resonant expects a file path")?;
+                    batch.get_or_insert_with(BatchConfig::default).resonant.push(path);
+                },
+                "--batch-out" => {
+                    batch.get_or_insert_with(BatchConfig::default).template = args.next().ok_or("--batch-out expects a path template")?;
+                },
+                "--batch-report" => {
+                    batch.get_or_insert_with(BatchConfig::default).report = Some(args.next().ok_or("--batch-report expects a file path")?);
+                },
+                "--batch-tail" => {
+                    let tail = args.next().ok_or("--batch-tail expects a number of seconds")?;
+                    batch.get_or_insert_with(BatchConfig::default).tail = tail.parse::<f64>()
+                        .ok()
+                        .filter(|t| *t >= 0.0)
+                        .ok_or_else(|| format!("Invalid tail length '{}'", tail))?;
+                },
+                "--jack" => {
+                    jack.get_or_insert_with(|| DEFAULT_JACK_NAME.to_string());
+                },
+                "--jack-name" => {
+                    jack = Some(args.next().ok_or("--jack-name expects a client name")?);
+                },
+                "--modulation" => {
+                    modulation_path = Some(args.next().ok_or("--modulation expects a file path")?);
+                },
+                "--shaping" => {
+                    shaping_path = Some(args.next().ok_or("--shaping expects a file path")?);
+                },
+                _ if arg.starts_with("--") => return Err(format!("Unknown argument '{}'", arg).into()),
+                _ => paths.push(arg),
+            }
+        }
+        if let Some(batch) = &batch {
+            if batch.sources.is_empty() {
+                return Err("Batch options need --batch <dir|glob> for the source files".into());
+            }
+        }
+        if jack.is_some() && !cfg!(feature = "jack") {
+            return Err("--jack needs a build with the jack feature".into());
+        }
+        let mut paths = paths.into_iter();
+        Ok(
+            Self {
+                audio_path: paths.next().unwrap_or_else(|| DEFAULT_AUDIO_PATH.to_string()),
+                r_audio_path: paths.next().unwrap_or_else(|| DEFAULT_R_AUDIO_PATH.to_string()),
+                analysis,
+                stereo,
+                midi,
+                osc_port,
+                record_path,
+                record_format,
+                automation_path,
+                keys_path,
+                session_path,
+                import,
+                batch,
+                jack,
+                modulation_path,
+                shaping_path,
+            }
+        )
+    }
+}
+
+fn window_names() -> String {
+    AnalysisWindow::ALL.iter().map(|w| w.name()).collect::<Vec<_>>().join(", ")
+}
+
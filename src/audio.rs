@@ -0,0 +1,42 @@
+use rodio::{Decoder, source::Source};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// the left and right channel of a stereo file and its sample rate
+#[inline]
+pub fn load_audio<P: AsRef<Path>>(path: P) -> Result<([Vec<f32>; 2], f64), Box<dyn Error>> {
+    let file = File::open(path)?;
+    let source = Decoder::new(BufReader::new(file))?;
+    let sample_rate = source.sample_rate() as f64;
+    let channels = source.channels();
+    if channels != 2 {
+        return Err("This app only supports audio files with 2 channels :C".into());
+    }
+    let mut samples = [Vec::new(), Vec::new()];
+    let mut cur = 0;
+    for v in source.convert_samples::<f32>() {
+        samples[cur].push(v);
+        cur = (cur + 1) % 2;
+    }
+    Ok((samples, sample_rate))
+}
+
+// (mono mix, channels, sample rate) in the f64 the planner works with
+pub fn load_resonant_audio<P: AsRef<Path>>(path: P) -> Result<(Vec<f64>, [Vec<f64>; 2], f64), Box<dyn Error>> {
+    let (channels, sample_rate) = load_audio(path)?;
+    let (audio, channels) = to_planner_audio(&channels);
+    Ok((audio, channels, sample_rate))
+}
+
+// the mono mix and both channels of stereo samples as f64
+pub fn to_planner_audio(channels: &[Vec<f32>; 2]) -> (Vec<f64>, [Vec<f64>; 2]) {
+    let [chan1, chan2] = channels;
+    let audio = chan1.iter().zip(chan2.iter()).map(|v| ((v.0 + v.1) / 2.0) as f64).collect::<Vec<f64>>();
+    let channels = [
+        chan1.iter().map(|v| *v as f64).collect::<Vec<f64>>(),
+        chan2.iter().map(|v| *v as f64).collect::<Vec<f64>>(),
+    ];
+    (audio, channels)
+}
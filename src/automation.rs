@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_AUTOMATION_PATH: &str = "./automation.json";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AutomationParam {
+    Decay,
+    Volume,
+    Transpose,
+    // the position between the two morph plans
+    Morph,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum AutomationMode {
+    #[default]
+    Off,
+    // slider moves are written into the lanes
+    Record,
+    // the lanes drive the parameters
+    Play,
+}
+
+impl AutomationMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AutomationMode::Off => "off",
+            AutomationMode::Record => "rec",
+            AutomationMode::Play => "play",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            AutomationMode::Off => AutomationMode::Record,
+            AutomationMode::Record => AutomationMode::Play,
+            AutomationMode::Play => AutomationMode::Off,
+        }
+    }
+}
+
+// (loc, value) points sorted by loc. a value holds from its loc until the next point
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct AutomationLane {
+    pub points: Vec<(usize, f64)>,
+}
+
+impl AutomationLane {
+    // the value at loc, or the first value before the first point
+    #[inline]
+    pub fn value_at(&self, loc: usize) -> Option<f64> {
+        let i = self.points.partition_point(|p| p.0 <= loc);
+        if i == 0 {
+            self.points.first().map(|p| p.1)
+        } else {
+            Some(self.points[i - 1].1)
+        }
+    }
+
+    #[inline]
+    fn insert(&mut self, loc: usize, value: f64) {
+        let i = self.points.partition_point(|p| p.0 <= loc);
+        // the latest move at a position wins
+        if i > 0 && self.points[i - 1].0 == loc {
+            self.points[i - 1].1 = value;
+        } else {
+            self.points.insert(i, (loc, value));
+        }
+    }
+}
+
+// automation lanes for the source track, positions are its playback position (AudioState::loc).
+// slider moves take effect at the start of the next buffer, which is the loc they are recorded at,
+// so playing the lanes back reproduces the recorded pass exactly
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Automation {
+    // sample rate of the source file the positions refer to
+    pub sample_rate: f64,
+    pub decay: AutomationLane,
+    pub volume: AutomationLane,
+    pub transpose: AutomationLane,
+    // missing in lanes saved before morphing could be automated
+    #[serde(default)]
+    pub morph: AutomationLane,
+    // saved with a session rather than with the lanes
+    #[serde(skip)]
+    mode: AutomationMode,
+    // lanes written to since recording started, they are cleared on the first write
+    #[serde(skip)]
+    touched: [bool; 4],
+}
+
+impl Automation {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            decay: AutomationLane::default(),
+            volume: AutomationLane::default(),
+            transpose: AutomationLane::default(),
+            morph: AutomationLane::default(),
+            mode: AutomationMode::Off,
+            touched: [false; 4],
+        }
+    }
+
+    #[inline]
+    pub fn mode(&self) -> AutomationMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: AutomationMode) {
+        if mode == AutomationMode::Record {
+            self.touched = [false; 4];
+        }
+        self.mode = mode;
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.mode == AutomationMode::Play
+    }
+
+    fn lane_mut(&mut self, param: AutomationParam) -> &mut AutomationLane {
+        match param {
+            AutomationParam::Decay => &mut self.decay,
+            AutomationParam::Volume => &mut self.volume,
+            AutomationParam::Transpose => &mut self.transpose,
+            AutomationParam::Morph => &mut self.morph,
+        }
+    }
+
+    // called when a slider changes a parameter, does nothing unless recording
+    pub fn record(&mut self, param: AutomationParam, loc: usize, value: f64) {
+        if self.mode != AutomationMode::Record {
+            return;
+        }
+        let index = param as usize;
+        if !self.touched[index] {
+            self.touched[index] = true;
+            self.lane_mut(param).points.clear();
+        }
+        self.lane_mut(param).insert(loc, value);
+    }
+
+    // (decay, volume, transpose, morph position) at loc, lanes without points keep the given values
+    #[inline]
+    pub fn values_at(&self, loc: usize, current: (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        (
+            self.decay.value_at(loc).unwrap_or(current.0),
+            self.volume.value_at(loc).unwrap_or(current.1),
+            self.transpose.value_at(loc).unwrap_or(current.2),
+            self.morph.value_at(loc).unwrap_or(current.3),
+        )
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // loads lanes recorded against a file with the given sample rate, rescaling their positions if needed
+    pub fn load<P: AsRef<Path>>(path: P, sample_rate: f64) -> Result<Self, Box<dyn Error>> {
+        let mut automation: Automation = serde_json::from_str(&fs::read_to_string(path)?)?;
+        automation.rescale(sample_rate);
+        Ok(automation)
+    }
+
+    // moves the positions of lanes recorded at another sample rate to the given one
+    pub fn rescale(&mut self, sample_rate: f64) {
+        if self.sample_rate == sample_rate {
+            return;
+        }
+        let ratio = sample_rate / self.sample_rate;
+        for lane in [&mut self.decay, &mut self.volume, &mut self.transpose, &mut self.morph] {
+            for point in lane.points.iter_mut() {
+                point.0 = (point.0 as f64 * ratio).round() as usize;
+            }
+        }
+        self.sample_rate = sample_rate;
+    }
+
+    // whether nothing has been recorded into any lane
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        [&self.decay, &self.volume, &self.transpose, &self.morph].iter().all(|lane| lane.points.is_empty())
+    }
+}
@@ -0,0 +1,199 @@
+use rayon::prelude::*;
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::audio::load_resonant_audio;
+use crate::recorder::{self, RecordFormat};
+use crate::planner::PlannerSettings;
+use crate::state::{AudioState, TrackParams};
+
+// frames rendered per call to AudioState::add_audio, about what the cpal callback gets
+const BLOCK_FRAMES: usize = 512;
+const DEFAULT_TEMPLATE: &str = "./batch/{source}__{resonant}.wav";
+const AUDIO_EXTENSIONS: [&str; 5] = ["wav", "flac", "mp3", "ogg", "aiff"];
+
+pub struct BatchConfig {
+    // a directory or a glob pattern
+    pub sources: String,
+    // falls back to the resonant file given on the command line when empty
+    pub resonant: Vec<String>,
+    // output path with {source}, {resonant} and {index} replaced
+    pub template: String,
+    // where the report goes, next to the outputs when None
+    pub report: Option<String>,
+    // seconds rendered after the source ends so the resonators can ring out
+    pub tail: f64,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            sources: String::new(),
+            resonant: Vec::new(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            report: None,
+            tail: 2.0,
+        }
+    }
+}
+
+struct JobResult {
+    source: String,
+    resonant: String,
+    output: String,
+    // (peak in dbfs, samples over full scale, length in seconds)
+    stats: Result<(f64, usize, f64), String>,
+}
+
+// renders every source with the plan of every resonant file, without the ui or an output device.
+// params are the source track's decay, transpose, volume, sidechain and shaping from the session
+pub fn run_batch(config: &BatchConfig, planner: &PlannerSettings, default_resonant: &str, params: &TrackParams, format: RecordFormat) -> Result<(), Box<dyn Error>> {
+    let sources = find_sources(&config.sources)?;
+    if sources.is_empty() {
+        return Err(format!("No audio files found for '{}'", config.sources).into());
+    }
+    let resonant = if config.resonant.is_empty() {
+        vec![default_resonant.to_string()]
+    } else {
+        config.resonant.clone()
+    };
+
+    // the plans of each resonant file, planned the same way as in the ui
+    let plans = resonant
+        .par_iter()
+        .map(|path| {
+            load_resonant_audio(path)
+                .and_then(|(_, channels, _)| planner.plan_channels(&channels))
+                .map_err(|e| format!("{:?}", e))
+        })
+        .collect::<Vec<_>>();
+
+    let jobs = sources
+        .iter()
+        .flat_map(|s| (0..resonant.len()).map(move |r| (s, r)))
+        .enumerate()
+        .collect::<Vec<_>>();
+    println!("Rendering {} sources with {} resonant files", sources.len(), resonant.len());
+
+    let results = jobs
+        .par_iter()
+        .map(|(index, (source, r))| {
+            let output = output_path(&config.template, source, &resonant[*r], *index);
+            let stats = match &plans[*r] {
+                Ok(plans) => render(source, plans, params, config.tail, &output, format).map_err(|e| format!("{:?}", e)),
+                Err(e) => Err(format!("planning failed: {}", e)),
+            };
+            JobResult {
+                source: source.display().to_string(),
+                resonant: resonant[*r].clone(),
+                output: output.display().to_string(),
+                stats,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let report_path = match &config.report {
+        Some(v) => PathBuf::from(v),
+        None => {
+            let dir = Path::new(&config.template).parent().unwrap_or(Path::new("."));
+            dir.join("batch-report.csv")
+        }
+    };
+    write_report(&report_path, &results)?;
+    let failed = results.iter().filter(|r| r.stats.is_err()).count();
+    println!("Rendered {} of {} files, report written to {}", results.len() - failed, results.len(), report_path.display());
+    Ok(())
+}
+
+fn find_sources(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let path = Path::new(pattern);
+    let mut sources = if path.is_dir() {
+        fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>()
+    } else {
+        glob::glob(pattern)?.filter_map(|p| p.ok()).collect::<Vec<_>>()
+    };
+    sources.retain(|p| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false)
+    });
+    sources.sort();
+    Ok(sources)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn output_path(template: &str, source: &Path, resonant: &str, index: usize) -> PathBuf {
+    PathBuf::from(
+        template
+            .replace("{source}", &file_stem(source))
+            .replace("{resonant}", &file_stem(Path::new(resonant)))
+            .replace("{index}", &index.to_string())
+    )
+}
+
+// plays the source through the resonator the way the cpal callback does and writes the result
+fn render(source: &Path, plans: &(ScaledResonatorPlan, ScaledResonatorPlan), params: &TrackParams, tail: f64, output: &Path, format: RecordFormat) -> Result<(f64, usize, f64), Box<dyn Error>> {
+    let mut audio_state = AudioState::init_audio_state(source)?;
+    // the parameters the source track has in the session, as in TrackSession::load_audio_state
+    audio_state.params = params.clone();
+    audio_state.set_plan(plans.clone(), None)?;
+    audio_state.old_decay = f64::NAN;
+    audio_state.old_transpose = f64::NAN;
+    audio_state.old_morph_pos = f64::NAN;
+    audio_state.looping = false;
+    audio_state.set_playing(true);
+
+    let mut out = Vec::new();
+    let mut block = vec![0.0_f32; 2 * BLOCK_FRAMES];
+    while audio_state.playing {
+        block.iter_mut().for_each(|v| *v = 0.0);
+        audio_state.add_audio(&mut block);
+        out.extend_from_slice(&block);
+    }
+    // the track has stopped, add_audio keeps running the resonators on silence
+    let tail_blocks = (tail * audio_state.sample_rate / BLOCK_FRAMES as f64).ceil() as usize;
+    for _ in 0..tail_blocks {
+        block.iter_mut().for_each(|v| *v = 0.0);
+        audio_state.add_audio(&mut block);
+        out.extend_from_slice(&block);
+    }
+
+    let peak = out.iter().fold(0.0_f32, |m, v| m.max(v.abs()));
+    let clipped = out.iter().filter(|v| v.abs() > 1.0).count();
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    recorder::write_wav(output, format, 2, audio_state.sample_rate as u32, &out)?;
+    let seconds = out.len() as f64 / 2.0 / audio_state.sample_rate;
+    Ok((20.0 * (peak as f64).max(1e-9).log10(), clipped, seconds))
+}
+
+fn write_report(path: &Path, results: &[JobResult]) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut report = String::from("source,resonant,output,peak_dbfs,clipped_samples,seconds,error\n");
+    for r in results {
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+        match &r.stats {
+            Ok((peak, clipped, seconds)) => report.push_str(&format!(
+                "{},{},{},{:.2},{},{:.3},\n",
+                quote(&r.source), quote(&r.resonant), quote(&r.output), peak, clipped, seconds,
+            )),
+            Err(e) => {
+                println!("Error occurred while rendering {} with {}: {}", r.source, r.resonant, e);
+                report.push_str(&format!("{},{},{},,,,{}\n", quote(&r.source), quote(&r.resonant), quote(&r.output), quote(e)));
+            },
+        }
+    }
+    fs::write(path, report)?;
+    Ok(())
+}
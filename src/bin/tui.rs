@@ -0,0 +1,438 @@
+// a terminal front end for machines where the druid window can't open, e.g. over ssh.
+// capstone-tui [source file] [resonant file] [--stereo] [--window <name>]
+use capstone_audio_demo::analysis::AnalysisWindow;
+use capstone_audio_demo::planner::{Analysis, PlannerSettings};
+use capstone_audio_demo::state::format_time;
+use capstone_audio_demo::{Engine, Track};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph};
+use ratatui::{Frame, Terminal};
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::error::Error;
+use std::io::stdout;
+use std::time::Duration;
+
+const DEFAULT_AUDIO_PATH: &str = "./audio/poem1b.wav";
+const DEFAULT_R_AUDIO_PATH: &str = "./audio/Datmosphere.wav";
+// how long to wait for a key before redrawing the transport
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+// how far the seek keys move the source playhead, in seconds
+const SEEK_STEP: f64 = 1.0;
+// how far the nudge keys move the selected parameter, as a fraction of its range
+const NUDGE_STEP: f64 = 0.01;
+// the nudge of H and L
+const COARSE_NUDGE_STEP: f64 = 0.1;
+// same colors as the druid ui
+const PURPLE: Color = Color::Rgb(0x7B, 0x61, 0x9E);
+const CYAN: Color = Color::Rgb(0x1e, 0xcb, 0xe1);
+const PINK: Color = Color::Rgb(0xe1, 0x5a, 0xb4);
+// eighths of a cell, for bars between rows
+const BAR_CHARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const HELP: &str = "space play  r play resonant  b build  ←/→ seek  0-9 jump  o loop  ↑/↓ select  -/+ or h/l adjust  H/L coarse  s stereo  w window  q quit";
+
+// the parameters the keys adjust, the same sliders as the druid ui
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Param {
+    MaxPeaks,
+    MinProminence,
+    MinThreshold,
+    MinFreq,
+    MaxFreq,
+    Decay,
+    Volume,
+    Transpose,
+}
+
+impl Param {
+    const ALL: [Param; 8] = [
+        Param::MaxPeaks,
+        Param::MinProminence,
+        Param::MinThreshold,
+        Param::MinFreq,
+        Param::MaxFreq,
+        Param::Decay,
+        Param::Volume,
+        Param::Transpose,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Param::MaxPeaks => "max peaks",
+            Param::MinProminence => "min prominence",
+            Param::MinThreshold => "min threshold",
+            Param::MinFreq => "min freq",
+            Param::MaxFreq => "max freq",
+            Param::Decay => "decay",
+            Param::Volume => "volume",
+            Param::Transpose => "transpose",
+        }
+    }
+
+    fn range(&self) -> (f64, f64) {
+        match self {
+            Param::Volume => (-40.0, 6.0),
+            Param::Transpose => (-1.0, 1.0),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    fn is_planner(&self) -> bool {
+        matches!(self, Param::MaxPeaks | Param::MinProminence | Param::MinThreshold | Param::MinFreq | Param::MaxFreq)
+    }
+
+    fn planner_field<'a>(&self, planner: &'a mut PlannerSettings) -> Option<&'a mut f64> {
+        match self {
+            Param::MaxPeaks => Some(&mut planner.max_peaks),
+            Param::MinProminence => Some(&mut planner.min_prominence),
+            Param::MinThreshold => Some(&mut planner.min_line),
+            Param::MinFreq => Some(&mut planner.min_range),
+            Param::MaxFreq => Some(&mut planner.max_range),
+            _ => None,
+        }
+    }
+
+    fn get(&self, engine: &Engine) -> f64 {
+        let planner = engine.planner();
+        match self {
+            Param::MaxPeaks => planner.max_peaks,
+            Param::MinProminence => planner.min_prominence,
+            Param::MinThreshold => planner.min_line,
+            Param::MinFreq => planner.min_range,
+            Param::MaxFreq => planner.max_range,
+            Param::Decay => engine.source().lock().params.decay,
+            Param::Volume => engine.source().lock().params.volume,
+            Param::Transpose => engine.source().lock().params.transpose,
+        }
+    }
+
+    fn set(&self, engine: &mut Engine, value: f64) {
+        if let Some(v) = self.planner_field(engine.planner_mut()) {
+            *v = value;
+            return;
+        }
+        match self {
+            Param::Decay => engine.set_decay(value),
+            Param::Volume => engine.set_volume(Track::Source, value),
+            _ => engine.set_transpose(value),
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self {
+            Param::Volume => format!("{:.1} db", value),
+            Param::Transpose => format!("{:+.2} oct", value),
+            Param::Decay => format!("{:.2}s ring", 4_f64.powf(value) - 1.0),
+            _ => format!("{:.3}", value),
+        }
+    }
+}
+
+struct App {
+    engine: Engine,
+    analysis: Analysis,
+    // the plan for each channel with the current planner settings
+    plans: (ScaledResonatorPlan, ScaledResonatorPlan),
+    selected: usize,
+    // the last thing that happened, shown above the help line
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    fn new(engine: Engine, status: String) -> Result<Self, Box<dyn Error>> {
+        let analysis = engine.analyze()?;
+        let plans = engine.plan_analyzed(&analysis);
+        Ok(Self {
+            engine,
+            analysis,
+            plans,
+            selected: 0,
+            status,
+            quit: false,
+        })
+    }
+
+    // after the analysis settings changed
+    fn reanalyze(&mut self) {
+        match self.engine.analyze() {
+            Ok(analysis) => {
+                self.analysis = analysis;
+                self.replan();
+            },
+            Err(e) => self.status = format!("Error occurred while analyzing: {:?}", e),
+        }
+    }
+
+    // after the planner settings changed
+    fn replan(&mut self) {
+        self.plans = self.engine.plan_analyzed(&self.analysis);
+    }
+
+    fn toggle_playing(&mut self, track: Track) {
+        let audio_state = match track {
+            Track::Source => self.engine.source(),
+            Track::Resonant => self.engine.resonant(),
+        };
+        let playing = !audio_state.lock().playing;
+        self.engine.set_playing(track, playing);
+    }
+
+    fn nudge(&mut self, steps: f64) {
+        let param = Param::ALL[self.selected];
+        let (min, max) = param.range();
+        let value = (param.get(&self.engine) + steps * (max - min)).max(min).min(max);
+        param.set(&mut self.engine, value);
+        if param.is_planner() {
+            self.replan();
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char(' ') => self.toggle_playing(Track::Source),
+            KeyCode::Char('r') => self.toggle_playing(Track::Resonant),
+            KeyCode::Char('b') => match self.engine.build_plan() {
+                Ok(plans) => {
+                    self.status = format!("built {} + {} resonators", plans.0.resonators.len(), plans.1.resonators.len());
+                    self.plans = plans;
+                },
+                Err(e) => self.status = format!("Error occurred while building resonator array: {:?}", e),
+            },
+            KeyCode::Left => self.engine.seek_by(Track::Source, -SEEK_STEP),
+            KeyCode::Right => self.engine.seek_by(Track::Source, SEEK_STEP),
+            KeyCode::Char(c @ '0'..='9') => self.engine.seek(Track::Source, c.to_digit(10).unwrap() as f64 / 10.0),
+            KeyCode::Char('o') => {
+                let looping = !self.engine.source().lock().looping;
+                self.engine.set_looping(Track::Source, looping);
+            },
+            KeyCode::Up | KeyCode::Char('k') => self.selected = (self.selected + Param::ALL.len() - 1) % Param::ALL.len(),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1) % Param::ALL.len(),
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('l') => self.nudge(NUDGE_STEP),
+            KeyCode::Char('-') | KeyCode::Char('h') => self.nudge(-NUDGE_STEP),
+            KeyCode::Char('L') => self.nudge(COARSE_NUDGE_STEP),
+            KeyCode::Char('H') => self.nudge(-COARSE_NUDGE_STEP),
+            KeyCode::Char('s') => {
+                let planner = self.engine.planner_mut();
+                planner.stereo = !planner.stereo;
+                self.reanalyze();
+            },
+            KeyCode::Char('w') => {
+                let planner = self.engine.planner_mut();
+                planner.analysis.window = planner.analysis.window.next();
+                self.status = format!("window: {}", planner.analysis.window.name());
+                self.reanalyze();
+            },
+            _ => {},
+        }
+    }
+
+    fn draw(&self, f: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(8),
+                Constraint::Length(Param::ALL.len() as u16 + 2),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ])
+            .split(f.size());
+        self.draw_transport(f, rows[0], Track::Source);
+        self.draw_transport(f, rows[1], Track::Resonant);
+        self.draw_spectrum(f, rows[2]);
+        self.draw_params(f, rows[3]);
+        f.render_widget(Paragraph::new(self.status.as_str()), rows[4]);
+        f.render_widget(Paragraph::new(HELP).style(Style::default().fg(Color::DarkGray)), rows[5]);
+    }
+
+    fn draw_transport(&self, f: &mut Frame, area: Rect, track: Track) {
+        let (name, audio_state) = match track {
+            Track::Source => ("source", self.engine.source().lock()),
+            Track::Resonant => ("resonant", self.engine.resonant().lock()),
+        };
+        let label = format!(
+            "{} {} / {}{}",
+            if audio_state.playing { "▶" } else { "⏸" },
+            format_time(audio_state.get_time()),
+            format_time(audio_state.get_duration()),
+            if audio_state.looping { "  loop" } else { "" },
+        );
+        let gauge = Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title(name))
+            .gauge_style(Style::default().fg(PURPLE))
+            .ratio(audio_state.get_progress().max(0.0).min(1.0))
+            .label(label);
+        f.render_widget(gauge, area);
+    }
+
+    // the spectrum as bars with the planned peaks marked above them, like the graph in the druid ui
+    fn draw_spectrum(&self, f: &mut Frame, area: Rect) {
+        let planner = self.engine.planner();
+        let block = Block::default().borders(Borders::ALL).title(format!(
+            "spectrum  {}  {} + {} peaks",
+            if planner.stereo { "stereo" } else { "mono" },
+            self.plans.0.resonators.len(),
+            self.plans.1.resonators.len(),
+        ));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let (width, height) = (inner.width as usize, inner.height as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // (value, color) of each column, the louder channel in stereo
+        let column_max = |spec: &[f64], c: usize| {
+            let start = c * spec.len() / width;
+            let end = ((c + 1) * spec.len() / width).max(start + 1).min(spec.len());
+            spec[start.min(end)..end].iter().copied().fold(0.0, f64::max)
+        };
+        let columns = (0..width)
+            .map(|c| match &self.analysis.channel_specs {
+                Some([left, right]) => {
+                    let (l, r) = (column_max(left, c), column_max(right, c));
+                    if l >= r { (l, CYAN) } else { (r, PINK) }
+                },
+                None => (column_max(&self.analysis.spec, c), CYAN),
+            })
+            .collect::<Vec<_>>();
+
+        // the marker drawn above each column holding a peak
+        let mut markers: Vec<Option<(char, Color)>> = vec![None; width];
+        let column_of = |arg: f64| ((arg / std::f64::consts::PI * width as f64) as usize).min(width - 1);
+        for arg in planner.excluded_peaks.iter() {
+            markers[column_of(*arg)] = Some(('x', Color::Gray));
+        }
+        let stereo = planner.stereo;
+        for (plan, color) in [(&self.plans.0, CYAN), (&self.plans.1, PINK)] {
+            for r in plan.resonators.iter() {
+                markers[column_of(r.0)] = Some(('▼', if stereo { color } else { Color::White }));
+            }
+        }
+
+        let threshold_row = ((1.0 - planner.min_line) * height as f64) as usize;
+        let range = |v: f64| ((v * width as f64) as usize).min(width - 1);
+        let (min_col, max_col) = (range(planner.min_range), range(planner.max_range));
+        let mut lines = Vec::with_capacity(height);
+        for row in 0..height {
+            // eighths of a cell filled from the bottom of this row
+            let row_base = ((height - 1 - row) * 8) as f64;
+            let spans = columns
+                .iter()
+                .enumerate()
+                .map(|(c, (value, color))| {
+                    let eighths = (value * (height * 8) as f64 - row_base).max(0.0).min(8.0) as usize;
+                    let bar_top = height - 1 - ((value * height as f64) as usize).min(height - 1);
+                    if eighths == 0 && row + 1 == bar_top {
+                        if let Some((marker, marker_color)) = markers[c] {
+                            return Span::styled(marker.to_string(), Style::default().fg(marker_color));
+                        }
+                    }
+                    if eighths > 0 {
+                        return Span::styled(BAR_CHARS[eighths].to_string(), Style::default().fg(*color));
+                    }
+                    if c == min_col || c == max_col {
+                        return Span::styled("│", Style::default().fg(Color::DarkGray));
+                    }
+                    if row == threshold_row && c > min_col && c < max_col {
+                        return Span::styled("─", Style::default().fg(Color::DarkGray));
+                    }
+                    Span::raw(" ")
+                })
+                .collect::<Vec<_>>();
+            lines.push(Line::from(spans));
+        }
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    fn draw_params(&self, f: &mut Frame, area: Rect) {
+        let mut lines = Vec::with_capacity(Param::ALL.len());
+        for (i, param) in Param::ALL.iter().enumerate() {
+            let value = param.get(&self.engine);
+            let (min, max) = param.range();
+            let filled = (((value - min) / (max - min)) * 20.0).round().max(0.0).min(20.0) as usize;
+            let style = if i == self.selected {
+                Style::default().fg(PURPLE).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} {:<15}", if i == self.selected { ">" } else { " " }, param.name()), style),
+                Span::styled(format!("[{}{}] ", "#".repeat(filled), "-".repeat(20 - filled)), style),
+                Span::styled(param.format(value), style),
+            ]));
+        }
+        let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("parameters"));
+        f.render_widget(paragraph, area);
+    }
+}
+
+fn parse_args() -> Result<(String, String, PlannerSettings), Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut planner = PlannerSettings::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stereo" => planner.stereo = true,
+            "--window" => {
+                let name = args.next().ok_or("--window expects a window name")?;
+                planner.analysis.window = AnalysisWindow::from_name(&name).ok_or_else(|| format!("Unknown window '{}'", name))?;
+            },
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg).into()),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() > 2 {
+        return Err("Expected at most a source file and a resonant file".into());
+    }
+    let mut paths = paths.into_iter();
+    Ok((
+        paths.next().unwrap_or_else(|| DEFAULT_AUDIO_PATH.to_string()),
+        paths.next().unwrap_or_else(|| DEFAULT_R_AUDIO_PATH.to_string()),
+        planner,
+    ))
+}
+
+fn run(app: &mut App) -> Result<(), Box<dyn Error>> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    terminal.clear()?;
+    while !app.quit {
+        terminal.draw(|f| app.draw(f))?;
+        if event::poll(REFRESH_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key.code);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (audio_path, r_audio_path, planner) = parse_args()?;
+    let mut engine = Engine::load(&audio_path, &r_audio_path)?;
+    engine.set_planner(planner);
+    // keep going without sound so the plan can still be explored
+    let status = match engine.start_stream() {
+        Ok(_) => format!("playing {} through the plan of {}", audio_path, r_audio_path),
+        Err(e) => format!("Error occurred while opening the output stream: {:?}", e),
+    };
+    let mut app = App::new(engine, status)?;
+
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    let result = run(&mut app);
+    // put the terminal back before reporting an error
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    result
+}
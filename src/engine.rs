@@ -0,0 +1,251 @@
+use parking_lot::Mutex;
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use crate::audio::to_planner_audio;
+use crate::automation::AutomationParam;
+use crate::modulation::ModulationSettings;
+use crate::planner::{Analysis, PlannerSettings};
+use crate::shaping::ShapingSettings;
+use crate::sidechain::SidechainSettings;
+use crate::state::{self, AudioState};
+use crate::stream::{prepare_cpal_stream, StreamTaps};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Track {
+    // the file that excites the resonators
+    Source,
+    // the file the plan is made from, played dry
+    Resonant,
+}
+
+// the two tracks, the planner and the output stream, without any ui. the gui shares the tracks
+// through source() and resonant(), other tools can drive everything through the methods here
+pub struct Engine {
+    source: Arc<Mutex<AudioState>>,
+    resonant: Arc<Mutex<AudioState>>,
+    // both channels of the resonant track as f64 for the planner
+    resonant_channels: [Vec<f64>; 2],
+    planner: PlannerSettings,
+    stream: Option<cpal::Stream>,
+    #[cfg(feature = "jack")]
+    jack: Option<crate::jack_backend::JackBackend>,
+}
+
+impl Engine {
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(source: P, resonant: Q) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(AudioState::init_audio_state(source)?, AudioState::init_audio_state(resonant)?))
+    }
+
+    // an engine for tracks that are already loaded, e.g. from a session or with AudioState::from_samples
+    pub fn new(mut source: AudioState, resonant: AudioState) -> Self {
+        let (_, resonant_channels) = to_planner_audio(resonant.samples());
+        source.sidechain.other = Some(Arc::clone(&resonant.meter));
+        Self {
+            source: Arc::new(Mutex::new(source)),
+            resonant: Arc::new(Mutex::new(resonant)),
+            resonant_channels,
+            planner: PlannerSettings::default(),
+            stream: None,
+            #[cfg(feature = "jack")]
+            jack: None,
+        }
+    }
+
+    // replaces the source track, keeping the stream running
+    pub fn load_source<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut audio_state = AudioState::init_audio_state(path)?;
+        audio_state.sidechain.other = Some(Arc::clone(&self.resonant.lock().meter));
+        *self.source.lock() = audio_state;
+        Ok(())
+    }
+
+    // replaces the resonant track, the next plan is made from it
+    pub fn load_resonant<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        let audio_state = AudioState::init_audio_state(path)?;
+        self.resonant_channels = to_planner_audio(audio_state.samples()).1;
+        self.source.lock().sidechain.other = Some(Arc::clone(&audio_state.meter));
+        *self.resonant.lock() = audio_state;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn source(&self) -> &Arc<Mutex<AudioState>> {
+        &self.source
+    }
+
+    #[inline]
+    pub fn resonant(&self) -> &Arc<Mutex<AudioState>> {
+        &self.resonant
+    }
+
+    #[inline]
+    fn track(&self, track: Track) -> &Arc<Mutex<AudioState>> {
+        match track {
+            Track::Source => &self.source,
+            Track::Resonant => &self.resonant,
+        }
+    }
+
+    #[inline]
+    pub fn planner(&self) -> &PlannerSettings {
+        &self.planner
+    }
+
+    #[inline]
+    pub fn planner_mut(&mut self) -> &mut PlannerSettings {
+        &mut self.planner
+    }
+
+    pub fn set_planner(&mut self, planner: PlannerSettings) {
+        self.planner = planner;
+    }
+
+    // the spectrum of the selected region of the resonant track
+    pub fn analyze(&self) -> Result<Analysis, Box<dyn Error>> {
+        self.planner.analyze(&self.resonant_channels)
+    }
+
+    // runs the planner on the resonant track, one plan per channel
+    pub fn plan(&self) -> Result<(ScaledResonatorPlan, ScaledResonatorPlan), Box<dyn Error>> {
+        self.planner.plan_channels(&self.resonant_channels)
+    }
+
+    // the plan for an analysis from analyze(), cheaper than plan() while only the planner settings change
+    pub fn plan_analyzed(&self, analysis: &Analysis) -> (ScaledResonatorPlan, ScaledResonatorPlan) {
+        self.planner.plan_analyzed(&self.resonant_channels, analysis)
+    }
+
+    // plans the resonant track and builds the source track's resonator from it, like BUILD RESONATOR
+    pub fn build_plan(&mut self) -> Result<(ScaledResonatorPlan, ScaledResonatorPlan), Box<dyn Error>> {
+        let plans = self.plan()?;
+        let region = Some((self.planner.region_start, self.planner.region_end));
+        self.apply_plan(plans.clone(), region, None)?;
+        Ok(plans)
+    }
+
+    // builds the source track's resonator from a plan made elsewhere, e.g. by import::import_plan.
+    // ring_time in seconds replaces the default decay
+    pub fn set_plan(&mut self, plans: (ScaledResonatorPlan, ScaledResonatorPlan), ring_time: Option<f64>) -> Result<(), Box<dyn Error>> {
+        self.apply_plan(plans, None, ring_time)
+    }
+
+    fn apply_plan(&mut self, plans: (ScaledResonatorPlan, ScaledResonatorPlan), region: Option<(f64, f64)>, ring_time: Option<f64>) -> Result<(), Box<dyn Error>> {
+        let mut audio_state = self.source.lock();
+        // a morph left from before doesn't match the new plan, as in BuildSnapshot::take. cleared
+        // first so set_plan shapes the gains of the plan itself
+        audio_state.morph = None;
+        audio_state.set_plan(plans, region)?;
+        audio_state.params.decay = ring_time.map(state::decay_for_ring_time).unwrap_or(state::DEFAULT_DECAY);
+        // apply the decay and transpose to the new arrays on the next buffer
+        audio_state.old_decay = f64::NAN;
+        audio_state.old_transpose = f64::NAN;
+        Ok(())
+    }
+
+    pub fn set_playing(&self, track: Track, playing: bool) {
+        self.track(track).lock().set_playing(playing);
+    }
+
+    pub fn set_looping(&self, track: Track, looping: bool) {
+        self.track(track).lock().looping = looping;
+    }
+
+    // values between 0.0 and 1.0
+    pub fn set_loop_region(&self, track: Track, start: f64, end: f64) {
+        self.track(track).lock().set_loop_region(start, end);
+    }
+
+    // moves the playhead to a position between 0.0 and 1.0
+    pub fn seek(&self, track: Track, pos: f64) {
+        self.track(track).lock().set_loc(pos);
+    }
+
+    // moves the playhead by a number of seconds, staying inside the file
+    pub fn seek_by(&self, track: Track, seconds: f64) {
+        self.track(track).lock().seek_by(seconds);
+    }
+
+    // in db, -40.0 to 6.0 in the ui
+    pub fn set_volume(&self, track: Track, volume: f64) {
+        let mut audio_state = self.track(track).lock();
+        audio_state.params.volume = volume;
+        audio_state.record_automation(AutomationParam::Volume);
+    }
+
+    // 0.0 to 1.0, the ring time is 4^decay - 1 seconds
+    pub fn set_decay(&self, decay: f64) {
+        let mut audio_state = self.source.lock();
+        audio_state.params.decay = decay;
+        audio_state.record_automation(AutomationParam::Decay);
+    }
+
+    // in octaves
+    pub fn set_transpose(&self, transpose: f64) {
+        let mut audio_state = self.source.lock();
+        audio_state.params.transpose = transpose;
+        audio_state.record_automation(AutomationParam::Transpose);
+    }
+
+    // the envelope follower moving the source track's decay, keyed from its input or the resonant track
+    pub fn set_sidechain(&self, settings: SidechainSettings) {
+        self.source.lock().params.sidechain = settings;
+    }
+
+    // the lfos, envelopes and routing of the source track, see modulation::ModulationSettings. like the
+    // sidechain it moves the resonators, which the resonant track doesn't have
+    pub fn set_modulation(&self, settings: ModulationSettings) {
+        self.source.lock().params.modulation = settings;
+    }
+
+    // gain and decay of each of the source track's resonators by frequency, see shaping::ShapingSettings
+    pub fn set_shaping(&self, settings: ShapingSettings) {
+        self.source.lock().set_shaping(settings);
+    }
+
+    // linear gain of the source audio going into the resonators
+    pub fn set_input_gain(&self, gain: f64) {
+        self.source.lock().input_gain = gain;
+    }
+
+    // adds the playing tracks to interleaved stereo data the way the output stream does, without an output
+    // device. the result isn't clamped so overs can be measured
+    pub fn render(&self, data: &mut [f32]) {
+        for v in data.iter_mut() {
+            *v = 0.0;
+        }
+        for track in [&self.source, &self.resonant] {
+            let mut audio_state = track.lock();
+            if audio_state.playing {
+                audio_state.add_audio(data);
+            } else {
+                audio_state.meter.clear();
+            }
+        }
+    }
+
+    // plays the tracks on the default output device until stop_stream is called or the engine is dropped
+    pub fn start_stream(&mut self) -> Result<StreamTaps, Box<dyn Error>> {
+        let (stream, taps) = prepare_cpal_stream(Arc::clone(&self.source), Arc::clone(&self.resonant))?;
+        self.stream = Some(stream);
+        Ok(taps)
+    }
+
+    // plays the tracks through a jack client instead, see jack_backend for the ports. stops the cpal stream
+    #[cfg(feature = "jack")]
+    pub fn start_jack(&mut self, client_name: &str) -> Result<StreamTaps, Box<dyn Error>> {
+        self.stream = None;
+        let (client, taps) = crate::jack_backend::start_jack(client_name, Arc::clone(&self.source), Arc::clone(&self.resonant))?;
+        self.jack = Some(client);
+        Ok(taps)
+    }
+
+    pub fn stop_stream(&mut self) {
+        self.stream = None;
+        #[cfg(feature = "jack")]
+        {
+            self.jack = None;
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const EXPORTS_DIR: &str = "./exports";
+// scale degrees closer than this are merged, in cents
+const MIN_STEP_CENTS: f64 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    // a SuperCollider Klank specification
+    Klank,
+    // a Faust program with one pm.modeFilter per resonator
+    Faust,
+    // a Scala tuning file of the peak frequencies folded into one octave
+    Scala,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Klank, ExportFormat::Faust, ExportFormat::Scala];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Klank => "klank",
+            ExportFormat::Faust => "faust",
+            ExportFormat::Scala => "scala",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|f| f == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Klank => "scd",
+            ExportFormat::Faust => "dsp",
+            ExportFormat::Scala => "scl",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct ExportedResonator {
+    pub freq: f64,
+    pub amp: f64,
+    // ring time in seconds, the value the resonator array is given by set_resonator_decays
+    pub decay: f64,
+}
+
+#[derive(Serialize)]
+struct ExportedPlan {
+    sample_rate: f64,
+    channels: Vec<Vec<ExportedResonator>>,
+}
+
+// the resonators of a plan in hz, all sharing one decay
+pub fn plan_resonators(plan: &ScaledResonatorPlan, sample_rate: f64, decay: f64) -> Vec<ExportedResonator> {
+    plan.resonators
+        .iter()
+        .map(|(arg, amp)| ExportedResonator {
+            freq: arg / (2.0 * std::f64::consts::PI) * sample_rate,
+            amp: *amp,
+            decay,
+        })
+        .collect()
+}
+
+// the channels to export, just one when both channels have the same plan
+fn channels(plans: &(ScaledResonatorPlan, ScaledResonatorPlan), sample_rate: f64, decay: f64) -> Vec<Vec<ExportedResonator>> {
+    if plans.0.resonators == plans.1.resonators {
+        vec![plan_resonators(&plans.0, sample_rate, decay)]
+    } else {
+        vec![plan_resonators(&plans.0, sample_rate, decay), plan_resonators(&plans.1, sample_rate, decay)]
+    }
+}
+
+// the plan in the given format. sample_rate is the rate the plan was built for
+pub fn export_plan(plans: &(ScaledResonatorPlan, ScaledResonatorPlan), sample_rate: f64, decay: f64, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+    let channels = channels(plans, sample_rate, decay);
+    let text = match format {
+        ExportFormat::Csv => to_csv(&channels),
+        ExportFormat::Json => serde_json::to_string_pretty(&ExportedPlan { sample_rate, channels })?,
+        ExportFormat::Klank => to_klank(&channels),
+        ExportFormat::Faust => to_faust(&channels),
+        ExportFormat::Scala => to_scala(&channels),
+    };
+    Ok(text)
+}
+
+// writes the plan to a new file in ./exports and returns its path
+pub fn export_plan_to_file(plans: &(ScaledResonatorPlan, ScaledResonatorPlan), sample_rate: f64, decay: f64, format: ExportFormat) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(EXPORTS_DIR)?;
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = Path::new(EXPORTS_DIR).join(format!("plan-{}.{}", secs, format.extension()));
+    fs::write(&path, export_plan(plans, sample_rate, decay, format)?)?;
+    Ok(path)
+}
+
+fn to_csv(channels: &[Vec<ExportedResonator>]) -> String {
+    let mut out = String::from("channel,freq_hz,amp,decay_s\n");
+    for (c, resonators) in channels.iter().enumerate() {
+        for r in resonators {
+            out.push_str(&format!("{},{},{},{}\n", c, r.freq, r.amp, r.decay));
+        }
+    }
+    out
+}
+
+fn join(values: impl Iterator<Item = f64>) -> String {
+    values.map(|v| format!("{}", v)).collect::<Vec<_>>().join(", ")
+}
+
+// `[freqs, amps, ring times] for each channel, ready for Klank.ar(~resonators[0], input)
+fn to_klank(channels: &[Vec<ExportedResonator>]) -> String {
+    let mut out = String::from("// one Klank specification per channel, e.g. { Klank.ar(~resonators[0], input) }\n~resonators = [\n");
+    for resonators in channels {
+        out.push_str(&format!(
+            "    `[[{}], [{}], [{}]],\n",
+            join(resonators.iter().map(|r| r.freq)),
+            join(resonators.iter().map(|r| r.amp)),
+            join(resonators.iter().map(|r| r.decay)),
+        ));
+    }
+    out.push_str("];\n");
+    out
+}
+
+fn to_faust(channels: &[Vec<ExportedResonator>]) -> String {
+    let mut out = String::from("import(\"stdfaust.lib\");\n\n");
+    let mut names = Vec::new();
+    for (c, resonators) in channels.iter().enumerate() {
+        let name = format!("bank{}", c);
+        let modes = resonators
+            .iter()
+            .map(|r| format!("    pm.modeFilter({}, {}, {})", r.freq, r.decay, r.amp))
+            .collect::<Vec<_>>();
+        if modes.is_empty() {
+            out.push_str(&format!("{} = _ : *(0);\n\n", name));
+        } else {
+            out.push_str(&format!("{} = _ <: (\n{}\n) :> _;\n\n", name, modes.join(",\n")));
+        }
+        names.push(name);
+    }
+    // a mono plan is used for both channels
+    if names.len() == 1 {
+        names.push(names[0].clone());
+    }
+    out.push_str(&format!("process = {};\n", names.join(", ")));
+    out
+}
+
+// the peaks of every channel folded into one octave above the lowest peak, with 2/1 as the period
+fn to_scala(channels: &[Vec<ExportedResonator>]) -> String {
+    let freqs = channels.iter().flatten().map(|r| r.freq).filter(|f| *f > 0.0).collect::<Vec<_>>();
+    let base = freqs.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut cents = freqs
+        .iter()
+        .map(|f| (1200.0 * (f / base).log2()) % 1200.0)
+        .filter(|c| *c >= MIN_STEP_CENTS && *c <= 1200.0 - MIN_STEP_CENTS)
+        .collect::<Vec<_>>();
+    cents.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    cents.dedup_by(|a, b| *a - *b < MIN_STEP_CENTS);
+
+    let mut out = String::from("! plan.scl\n!\n");
+    if base.is_finite() {
+        out.push_str(&format!("Resonator peaks, 1/1 = {:.4} Hz\n", base));
+    } else {
+        out.push_str("Resonator peaks (empty plan)\n");
+    }
+    out.push_str(&format!(" {}\n!\n", cents.len() + 1));
+    for c in cents {
+        out.push_str(&format!(" {:.5}\n", c));
+    }
+    out.push_str(" 2/1\n");
+    out
+}
@@ -5,39 +5,44 @@ use druid::kurbo::{BezPath, Point, Circle};
 use resonator_builder::scaled_builder::*;
 use parking_lot::Mutex;
 use std::sync::Arc;
-use resonator_builder::fft::{FftCalculator, window::WindowFunction};
 use std::path::Path;
-use crate::load_audio;
+use crate::audio::load_resonant_audio;
+use crate::analysis::compute_spectrogram;
+use crate::planner::{self, Analysis, PlannerSettings};
+use crate::import::ImportedPlan;
 use std::error::Error;
 
-const MAX_PEAKS: usize = 2000;
+// how close a click has to be to a peak to toggle it, in pixels
+const PEAK_HIT_RADIUS: f64 = 8.0;
 
 #[derive(Clone, Data, Lens)]
 pub struct GraphData {
-    // values between 0.0 and 1.0
-    #[data(ignore)]
-    pub spec: Vec<f64>,
+    pub sample_rate: f64,
 
+    // both channels of the whole resonant file and its stft, see analysis::compute_spectrogram
     #[data(ignore)]
-    pub audio: Vec<f64>,
-    pub sample_rate: f64,
+    pub full_channels: Arc<[Vec<f64>; 2]>,
+    #[data(ignore)]
+    pub spectrogram: Arc<Vec<Vec<f64>>>,
 
-    // value between 0.0 and 1.0
-    pub min_line: f64,
+    // the region, analysis and planner settings the sliders and buttons edit. the region is
+    // snapped to whole samples by select_region
+    #[data(same_fn = "PartialEq::eq")]
+    pub planner: PlannerSettings,
 
+    // the spectrum of the selected region the planner picks its peaks from, replaced by reanalyze
+    pub analysis: Arc<Analysis>,
+
+    // the plans drawn last, in mono and in stereo
     #[data(ignore)]
     pub plan: Arc<Mutex<ScaledResonatorPlan>>,
+    #[data(ignore)]
+    pub channel_plans: Arc<Mutex<[ScaledResonatorPlan; 2]>>,
 
-    pub min_range: f64,
-    pub max_range: f64,
-
-    pub min_prominence: f64,
-    pub max_peaks: f64,
-
-    // the difference between the highest and lowest value in the spectrum
-    pub spectrum_scale: f64,
-    // the lowest value displayed in the spectrum
-    pub spectrum_base: f64,
+    // a plan for each channel imported from a file or spec, used instead of the planner when set
+    pub imported: Option<Arc<(ScaledResonatorPlan, ScaledResonatorPlan)>>,
+    // ring time in seconds that came with the imported plan
+    pub imported_decay: Option<f64>,
 }
 
 impl GraphData {
@@ -46,64 +51,144 @@ impl GraphData {
         height - value * height
     }
 
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
-        let (audio, spec, spectrum_scale, spectrum_base, sample_rate) = load_resonant_audio(path, 1000)?;
+    pub fn new<P: AsRef<Path>>(path: P, settings: &PlannerSettings) -> Result<Self, Box<dyn Error>> {
+        let (audio, channels, sample_rate) = load_resonant_audio(path)?;
+        let spectrogram = compute_spectrogram(&audio[..])?;
+        let mut planner = settings.clone();
+        let length = channels[0].len();
+        let (start_idx, end_idx) = planner::region_bounds(length, settings.region_start, settings.region_end);
+        planner.region_start = start_idx as f64 / length as f64;
+        planner.region_end = end_idx as f64 / length as f64;
+        let analysis = planner.analyze(&channels)?;
         Ok(
             Self {
-                spec,
-                audio,
                 sample_rate,
-
-                min_line: 0.0,
+                full_channels: Arc::new(channels),
+                spectrogram: Arc::new(spectrogram),
+                planner,
+                analysis: Arc::new(analysis),
                 plan: Arc::new(Mutex::new(ScaledResonatorPlan::empty())),
-
-                min_range: 0.0,
-                max_range: 0.5,
-                min_prominence: 0.3,
-                max_peaks: 0.1,
-
-                spectrum_base,
-                spectrum_scale,
+                channel_plans: Arc::new(Mutex::new([ScaledResonatorPlan::empty(), ScaledResonatorPlan::empty()])),
+                imported: None,
+                imported_decay: None,
             }
         )
     }
-}
 
-#[inline]
-fn load_resonant_audio<P: AsRef<Path>>(path: P, resolution: usize) -> Result<(Vec<f64>, Vec<f64>, f64, f64, f64), Box<dyn Error>> {
-    let ([chan1, chan2], sample_rate) = load_audio(path)?;
-    let audio = chan1.into_iter().zip(chan2.into_iter()).map(|v| ((v.0 + v.1) / 2.0) as f64).collect::<Vec<f64>>();
-    let near_pow_2 = ((audio.len() - 1).ilog2() + 1) as usize;
-    let fft_size = 2_usize.pow(near_pow_2 as u32);
-    let mut fft = FftCalculator::new(audio.len(), fft_size - audio.len())?;
-    let comp_freqs = fft.real_fft(&audio[..], resonator_builder::fft::window::Rectangular::real_window);
-    
-    let freqs = comp_freqs[0..fft_size / 2]
-        .into_iter()
-        .map(|v| v.norm().log10())
-        .collect::<Vec<f64>>();
-    
-    let mut global_max = f64::MIN;
-    for v in &freqs {
-        if *v > global_max {
-            global_max = *v;
+    // restrict analysis to a time region of the resonant file and recompute the spectrum
+    pub fn select_region(&mut self, start: f64, end: f64) -> Result<(), Box<dyn Error>> {
+        let length = self.full_channels[0].len();
+        let (start_idx, end_idx) = planner::region_bounds(length, start, end);
+        self.planner.region_start = start_idx as f64 / length as f64;
+        self.planner.region_end = end_idx as f64 / length as f64;
+        self.reanalyze()
+    }
+
+    // recompute the spectrum of the selected region, e.g. after the analysis settings changed
+    pub fn reanalyze(&mut self) -> Result<(), Box<dyn Error>> {
+        self.analysis = Arc::new(self.planner.analyze(&self.full_channels)?);
+        Ok(())
+    }
+
+    // the plan for each channel with the current settings, the mono plan twice when not analyzing in stereo.
+    // computed here rather than taken from the paint cache so changes that have not been drawn yet are included
+    pub fn current_plans(&self) -> (ScaledResonatorPlan, ScaledResonatorPlan) {
+        if let Some(imported) = &self.imported {
+            return (self.without_excluded(&imported.0), self.without_excluded(&imported.1));
+        }
+        self.planner.plan_analyzed(&self.full_channels, &self.analysis)
+    }
+
+    // the spectrum a channel's peaks are drawn on, the mono spectrum when not analyzing in stereo
+    #[inline]
+    fn channel_spec(&self, channel: usize) -> &[f64] {
+        match &self.analysis.channel_specs {
+            Some(specs) => &specs[channel],
+            None => &self.analysis.spec,
+        }
+    }
+
+    // replaces the planner settings, e.g. from the undo history, analyzing again when the
+    // region or the analysis changed
+    pub fn set_planner(&mut self, settings: PlannerSettings) -> Result<(), Box<dyn Error>> {
+        let region_changed = (settings.region_start, settings.region_end) != (self.planner.region_start, self.planner.region_end);
+        let analysis_changed = settings.analysis != self.planner.analysis || settings.stereo != self.planner.stereo;
+        self.planner = settings;
+        if region_changed {
+            self.select_region(self.planner.region_start, self.planner.region_end)
+        } else if analysis_changed {
+            self.reanalyze()
+        } else {
+            Ok(())
         }
     }
-    let min_value = -3.0;
-    let scale = global_max - min_value;
-    let mut cur_bin = 0;
-    let mut out = Vec::with_capacity(resolution);
-    for i in 0..resolution {
-        let mut max = f64::MIN;
-        while (cur_bin as f64 / freqs.len() as f64) < ((i + 1) as f64 / resolution as f64) {
-            if max < freqs[cur_bin] {
-                max = freqs[cur_bin];
+
+    fn without_excluded(&self, plan: &ScaledResonatorPlan) -> ScaledResonatorPlan {
+        let mut plan = plan.clone();
+        plan.resonators.retain(|r| !self.is_excluded(r.0));
+        plan
+    }
+
+    // use a plan made without the planner, see import::import_plan
+    pub fn set_imported(&mut self, imported: ImportedPlan) {
+        self.imported = Some(Arc::new(imported.plans));
+        self.imported_decay = imported.decay;
+    }
+
+    // go back to planning from the resonant file
+    pub fn clear_imported(&mut self) {
+        self.imported = None;
+        self.imported_decay = None;
+    }
+
+    #[inline]
+    fn is_excluded(&self, arg: f64) -> bool {
+        self.planner.is_excluded(arg)
+    }
+
+    // removes the excluded peak or planned peak drawn closest to pos, if any is within reach.
+    // a planned peak is excluded, an excluded peak is added back
+    pub fn toggle_peak_at(&mut self, pos: Point, size: Size) -> bool {
+        let nearest = |args: &mut dyn Iterator<Item = f64>, spec: &[f64]| {
+            args.map(|arg| (arg, peak_point(arg, spec, size).distance(pos)))
+                .filter(|(_, d)| *d < PEAK_HIT_RADIUS)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        };
+
+        let excluded = nearest(&mut self.planner.excluded_peaks.iter().copied(), &self.analysis.spec);
+        if let Some((arg, _)) = excluded {
+            self.planner.excluded_peaks.retain(|v| *v != arg);
+            return true;
+        }
+
+        let planned = if self.planner.stereo {
+            let plans = self.channel_plans.lock();
+            let left = nearest(&mut plans[0].resonators.iter().map(|r| r.0), self.channel_spec(0));
+            let right = nearest(&mut plans[1].resonators.iter().map(|r| r.0), self.channel_spec(1));
+            match (left, right) {
+                (Some(l), Some(r)) => Some(if l.1 <= r.1 { l } else { r }),
+                (l, r) => l.or(r),
             }
-            cur_bin += 1;
+        } else {
+            nearest(&mut self.plan.lock().resonators.iter().map(|r| r.0), &self.analysis.spec)
+        };
+        match planned {
+            Some((arg, _)) => {
+                self.planner.excluded_peaks.push(arg);
+                true
+            },
+            None => false,
         }
-        out.push((max.max(min_value) - min_value) / scale)
     }
-    Ok((audio, out, scale, min_value, sample_rate))
+}
+
+// where a peak is drawn on a graph of the given size
+#[inline]
+fn peak_point(arg: f64, spec: &[f64], size: Size) -> Point {
+    let x = arg / std::f64::consts::PI;
+    let y = ((x * spec.len() as f64) as usize).min(spec.len().saturating_sub(1));
+    let value = spec.get(y).copied().unwrap_or(0.0);
+    Point::new(x * size.width, GraphData::value_to_pixel(size.height, value))
 }
 
 // a custom widget that draws a line graph
@@ -117,7 +202,14 @@ impl LineGraph {
 }
 
 impl Widget<GraphData> for LineGraph {
-    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, _data: &mut GraphData, _env: &Env) {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut GraphData, _env: &Env) {
+        // clicking a peak removes it from the plan, clicking a removed peak brings it back
+        if let Event::MouseDown(mouse_event) = event {
+            if data.toggle_peak_at(mouse_event.pos, ctx.size()) {
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+        }
     }
 
     fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &GraphData, _env: &Env) {}
@@ -144,64 +236,77 @@ impl Widget<GraphData> for LineGraph {
 
         // create a color for the line graph
         let color = Color::rgb8(0x1e, 0xcb, 0xe1);
+        let r_color = Color::rgb8(0xe1, 0x5a, 0xb4);
 
         let grey = Color::grey(0.8);
-        // create a path for the line graph
-        let mut path = BezPath::new();
-
-        // move to the first point of the line graph
-        if let Some(first) = data.spec.first() {
-            path.move_to(Point::new(0.0, GraphData::value_to_pixel(size.height, *first)));
-        }
 
-        // add lines to the rest of the points of the line graph
-        for (i, value) in data.spec.iter().enumerate().skip(1) {
-            path.line_to(Point::new(i as f64 * size.width / (data.spec.len() - 1) as f64, GraphData::value_to_pixel(size.height, *value)));
+        match &data.analysis.channel_specs {
+            Some(specs) => {
+                draw_spectrum(ctx, &specs[0], &color);
+                draw_spectrum(ctx, &specs[1], &r_color);
+            },
+            None => draw_spectrum(ctx, &data.analysis.spec, &color),
         }
 
-        // stroke the path with some thickness
-        ctx.stroke(path, &color, 2.0);
-
         let mut path = BezPath::new();
-        path.move_to(Point::new(0.0, GraphData::value_to_pixel(size.height, data.min_line)));
-        path.line_to(Point::new(size.width, GraphData::value_to_pixel(size.height, data.min_line)));
+        path.move_to(Point::new(0.0, GraphData::value_to_pixel(size.height, data.planner.min_line)));
+        path.line_to(Point::new(size.width, GraphData::value_to_pixel(size.height, data.planner.min_line)));
         ctx.stroke(path, &grey, 2.0);
 
-        let plan;
-        if data.min_range >= data.max_range {
-            plan = ScaledResonatorPlan::empty();
+        let (plan1, plan2) = data.current_plans();
+        if data.planner.stereo {
+            draw_peaks(ctx, &plan1, data.channel_spec(0), &Color::rgba8(0x1e, 0xcb, 0xe1, 96));
+            draw_peaks(ctx, &plan2, data.channel_spec(1), &Color::rgba8(0xe1, 0x5a, 0xb4, 96));
+            *data.channel_plans.lock() = [plan1, plan2];
         } else {
-            plan = ScaledResonatorPlanner::new()
-                .with_min_prominence(data.min_prominence * data.spectrum_scale)
-                .with_max_num_peaks((data.max_peaks * MAX_PEAKS as f64) as usize)
-                .with_min_freq(data.min_range)
-                .with_max_freq(data.max_range)
-                .with_min_threshold(data.min_line * data.spectrum_scale + data.spectrum_base)
-                .plan(&data.audio[..]);
+            draw_peaks(ctx, &plan1, &data.analysis.spec, &Color::rgba8(255, 255, 255, 64));
+            let mut new_plan = data.plan.lock();
+            *new_plan = plan1;
+            std::mem::drop(new_plan);
         }
 
-        for peak in &plan.resonators {
-            let x = peak.0 / std::f64::consts::PI;
-            let y = (x * data.spec.len() as f64) as usize;
-            let circle = Circle::new(Point::new(x * size.width, GraphData::value_to_pixel(size.height, data.spec[y])), 5.0);
-            ctx.fill(circle, &Color::rgba8(255, 255, 255, 64))
+        for arg in data.planner.excluded_peaks.iter() {
+            let circle = Circle::new(peak_point(*arg, &data.analysis.spec, size), 5.0);
+            ctx.stroke(circle, &Color::rgba8(255, 255, 255, 128), 1.0);
         }
 
-        let mut new_plan = data.plan.lock();
-        *new_plan = plan;
-        std::mem::drop(new_plan);
-
         let mut path = BezPath::new();
-        path.move_to(Point::new(data.min_range * size.width, 0.0));
-        path.line_to(Point::new(data.min_range * size.width, size.height));
+        path.move_to(Point::new(data.planner.min_range * size.width, 0.0));
+        path.line_to(Point::new(data.planner.min_range * size.width, size.height));
         ctx.stroke(path, &grey, 2.0);
 
         let mut path = BezPath::new();
-        path.move_to(Point::new(data.max_range * size.width, 0.0));
-        path.line_to(Point::new(data.max_range * size.width, size.height));
+        path.move_to(Point::new(data.planner.max_range * size.width, 0.0));
+        path.line_to(Point::new(data.planner.max_range * size.width, size.height));
         ctx.stroke(path, &grey, 2.0);
 
-        let selected = Rect::new( data.min_range * size.width, 0.0, data.max_range * size.width, GraphData::value_to_pixel(size.height, data.min_line));
+        let selected = Rect::new( data.planner.min_range * size.width, 0.0, data.planner.max_range * size.width, GraphData::value_to_pixel(size.height, data.planner.min_line));
         ctx.fill(selected, &Color::rgba8(255, 255, 255, 20));
     }
-}
\ No newline at end of file
+}
+fn draw_spectrum(ctx: &mut PaintCtx, spec: &[f64], color: &Color) {
+    let size = ctx.size();
+    // create a path for the line graph
+    let mut path = BezPath::new();
+
+    // move to the first point of the line graph
+    if let Some(first) = spec.first() {
+        path.move_to(Point::new(0.0, GraphData::value_to_pixel(size.height, *first)));
+    }
+
+    // add lines to the rest of the points of the line graph
+    for (i, value) in spec.iter().enumerate().skip(1) {
+        path.line_to(Point::new(i as f64 * size.width / (spec.len() - 1) as f64, GraphData::value_to_pixel(size.height, *value)));
+    }
+
+    // stroke the path with some thickness
+    ctx.stroke(path, color, 2.0);
+}
+
+fn draw_peaks(ctx: &mut PaintCtx, plan: &ScaledResonatorPlan, spec: &[f64], color: &Color) {
+    let size = ctx.size();
+    for peak in &plan.resonators {
+        let circle = Circle::new(peak_point(peak.0, spec, size), 5.0);
+        ctx.fill(circle, color)
+    }
+}
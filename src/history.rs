@@ -0,0 +1,161 @@
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use parking_lot::Mutex;
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::sync::Arc;
+use crate::AppState;
+use crate::graph::GraphData;
+use crate::morph::MorphPlan;
+use crate::planner::PlannerSettings;
+use crate::state::AudioState;
+
+// oldest edits are forgotten past this
+const MAX_HISTORY: usize = 200;
+
+// the plan the source track's resonator was built from
+pub struct BuildSnapshot {
+    plan: Option<(ScaledResonatorPlan, ScaledResonatorPlan)>,
+    plan_region: Option<(f64, f64)>,
+    morph: Option<(MorphPlan, MorphPlan)>,
+}
+
+impl BuildSnapshot {
+    // moves the plan out of the audio state, call before building a new one
+    pub fn take(audio_state: &mut AudioState) -> Self {
+        Self {
+            plan: audio_state.plan.take(),
+            plan_region: audio_state.plan_region.take(),
+            morph: audio_state.morph.take(),
+        }
+    }
+
+    // puts the plan back and rebuilds its resonator array, keeping the current decay and transpose
+    fn restore(self, audio_state: &mut AudioState) {
+        audio_state.filter = match &self.plan {
+            Some((plan1, plan2)) => match (plan1.build_resonator_array(audio_state.sample_rate), plan2.build_resonator_array(audio_state.sample_rate)) {
+                (Ok(v1), Ok(v2)) => Some((v1, v2)),
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("Error occurred while building resonator array: {:?}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        audio_state.plan = self.plan;
+        audio_state.plan_region = self.plan_region;
+        audio_state.morph = self.morph;
+        // the new arrays need the decay, transpose and morph position applied again
+        audio_state.old_decay = f64::NAN;
+        audio_state.old_morph_pos = f64::NAN;
+        audio_state.apply_shaping();
+    }
+}
+
+enum Edit {
+    // the region, analysis, planner parameters and peak exclusions of GraphData
+    Planner(PlannerSettings),
+    Build(BuildSnapshot),
+}
+
+// undo and redo stacks of planner edits and resonator builds. each entry holds the state before the edit
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn push_planner(&mut self, before: PlannerSettings) {
+        self.push(Edit::Planner(before));
+    }
+
+    pub fn push_build(&mut self, before: BuildSnapshot) {
+        self.push(Edit::Build(before));
+    }
+
+    pub fn undo(&mut self, graph: &mut GraphData, audio_state: &Arc<Mutex<AudioState>>) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                let current = swap(edit, graph, audio_state);
+                self.redo.push(current);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, graph: &mut GraphData, audio_state: &Arc<Mutex<AudioState>>) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                let current = swap(edit, graph, audio_state);
+                self.undo.push(current);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+// restores the edit and returns the state it replaced
+fn swap(edit: Edit, graph: &mut GraphData, audio_state: &Arc<Mutex<AudioState>>) -> Edit {
+    match edit {
+        Edit::Planner(settings) => {
+            let current = graph.planner.clone();
+            if let Err(e) = graph.set_planner(settings) {
+                println!("Error occurred while analyzing the resonant file: {:?}", e);
+            }
+            Edit::Planner(current)
+        },
+        Edit::Build(snapshot) => {
+            let mut audio_state = audio_state.lock();
+            let current = BuildSnapshot::take(&mut audio_state);
+            snapshot.restore(&mut audio_state);
+            Edit::Build(current)
+        },
+    }
+}
+
+// records planner edits made with the mouse as one entry per drag or click.
+// undo and redo are bound in keys::KeyController
+pub struct HistoryController {
+    // planner settings when the mouse went down
+    before: Option<PlannerSettings>,
+}
+
+impl HistoryController {
+    pub fn new() -> Self {
+        Self {
+            before: None,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for HistoryController {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::MouseDown(_) = event {
+            self.before = Some(data.line_graph.planner.clone());
+        }
+        child.event(ctx, event, data, env);
+        if let Event::MouseUp(_) = event {
+            if let Some(before) = self.before.take() {
+                if before != data.line_graph.planner {
+                    data.history.lock().push_planner(before);
+                }
+            }
+        }
+    }
+}
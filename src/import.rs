@@ -0,0 +1,236 @@
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// the octave used when a spec gives a note without one
+const DEFAULT_OCTAVE: i32 = 4;
+// imported resonators above this fraction of the nyquist frequency are dropped
+const MAX_NYQUIST_FRACTION: f64 = 0.99;
+
+// a plan made without the planner, see GraphData::imported
+pub struct ImportedPlan {
+    pub plans: (ScaledResonatorPlan, ScaledResonatorPlan),
+    // ring time in seconds when the source gave one, the resonator array only has a single decay
+    pub decay: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct JsonResonator {
+    freq: f64,
+    #[serde(default = "default_amp")]
+    amp: f64,
+    decay: Option<f64>,
+}
+
+fn default_amp() -> f64 {
+    1.0
+}
+
+// either the format written by export::ExportFormat::Json or a plain list of resonators
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPlan {
+    Channels { channels: Vec<Vec<JsonResonator>> },
+    List(Vec<JsonResonator>),
+}
+
+// (freq, amp, decay) of each resonator of each channel
+type Channels = Vec<Vec<(f64, f64, Option<f64>)>>;
+
+// imports a .csv or .json file, or else reads the text as a spec like "C major triad harmonics 1-16".
+// sample_rate is the rate the plan will be built for
+pub fn import_plan(text: &str, sample_rate: f64) -> Result<ImportedPlan, Box<dyn Error>> {
+    let text = text.trim();
+    let path = Path::new(text);
+    let channels = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => parse_csv(&fs::read_to_string(path)?)?,
+        Some("json") => parse_json(&fs::read_to_string(path)?)?,
+        _ => vec![parse_spec(text, sample_rate)?],
+    };
+    to_plan(channels, sample_rate)
+}
+
+fn to_plan(channels: Channels, sample_rate: f64) -> Result<ImportedPlan, Box<dyn Error>> {
+    if channels.iter().all(|c| c.is_empty()) {
+        return Err("The imported plan has no resonators".into());
+    }
+    let max_freq = sample_rate / 2.0 * MAX_NYQUIST_FRACTION;
+    let decays = channels.iter().flatten().filter_map(|r| r.2).collect::<Vec<_>>();
+    let decay = if decays.is_empty() {
+        None
+    } else {
+        let mean = decays.iter().sum::<f64>() / decays.len() as f64;
+        if decays.iter().any(|d| (d - mean).abs() > 1e-9) {
+            println!("The resonator array has a single decay, using the mean decay {:.3}s of the imported resonators", mean);
+        }
+        Some(mean)
+    };
+
+    let mut plans = channels.into_iter().map(|resonators| {
+        let mut plan = ScaledResonatorPlan::empty();
+        plan.resonators = resonators
+            .into_iter()
+            .filter(|r| r.0 > 0.0 && r.0 < max_freq)
+            .map(|r| (r.0 / sample_rate * 2.0 * std::f64::consts::PI, r.1))
+            .collect();
+        plan
+    });
+    let left = plans.next().unwrap();
+    // a single channel is used for both
+    let right = match plans.next() {
+        Some(v) => v,
+        None => left.clone(),
+    };
+    Ok(ImportedPlan { plans: (left, right), decay })
+}
+
+// freq[,amp[,decay]] per line, or the columns named in a header such as the one export writes
+fn parse_csv(text: &str) -> Result<Channels, Box<dyn Error>> {
+    let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')).peekable();
+    let mut columns = (None, 0, Some(1), Some(2));
+    if let Some(header) = lines.peek() {
+        if header.split(',').any(|v| v.trim().parse::<f64>().is_err()) {
+            let names = header.split(',').map(|v| v.trim().to_lowercase()).collect::<Vec<_>>();
+            let find = |prefix: &str| names.iter().position(|n| n.starts_with(prefix));
+            columns = (
+                find("channel"),
+                find("freq").ok_or("CSV header has no freq column")?,
+                find("amp"),
+                find("decay"),
+            );
+            lines.next();
+        }
+    }
+    let (channel_col, freq_col, amp_col, decay_col) = columns;
+
+    let mut channels: Channels = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let values = line.split(',').map(|v| v.trim()).collect::<Vec<_>>();
+        let number = |col: Option<usize>| -> Result<Option<f64>, Box<dyn Error>> {
+            match col.and_then(|c| values.get(c)) {
+                Some(v) if !v.is_empty() => Ok(Some(v.parse::<f64>().map_err(|e| format!("Invalid number '{}' on line {}: {}", v, i + 1, e))?)),
+                _ => Ok(None),
+            }
+        };
+        let freq = number(Some(freq_col))?.ok_or_else(|| format!("Missing frequency on line {}", i + 1))?;
+        let amp = number(amp_col)?.unwrap_or(1.0);
+        let decay = number(decay_col)?;
+        let channel = number(channel_col)?.unwrap_or(0.0) as usize;
+        if channel > 1 {
+            return Err(format!("Only channels 0 and 1 are supported, got {} on line {}", channel, i + 1).into());
+        }
+        while channels.len() <= channel {
+            channels.push(Vec::new());
+        }
+        channels[channel].push((freq, amp, decay));
+    }
+    Ok(channels)
+}
+
+fn parse_json(text: &str) -> Result<Channels, Box<dyn Error>> {
+    let channels = match serde_json::from_str::<JsonPlan>(text)? {
+        JsonPlan::Channels { channels } => channels,
+        JsonPlan::List(list) => vec![list],
+    };
+    Ok(channels
+        .into_iter()
+        .map(|c| c.into_iter().map(|r| (r.freq, r.amp, r.decay)).collect())
+        .collect())
+}
+
+// semitones above the root of each chord or scale
+fn intervals(name: &str) -> Option<&'static [i32]> {
+    let intervals: &[i32] = match name {
+        "" | "note" | "harmonic series" => &[0],
+        "major" | "major triad" | "major chord" => &[0, 4, 7],
+        "minor" | "minor triad" | "minor chord" => &[0, 3, 7],
+        "diminished" | "diminished triad" => &[0, 3, 6],
+        "augmented" | "augmented triad" => &[0, 4, 8],
+        "sus2" | "sus2 triad" => &[0, 2, 7],
+        "sus4" | "sus4 triad" => &[0, 5, 7],
+        "major seventh" | "maj7" => &[0, 4, 7, 11],
+        "minor seventh" | "min7" | "m7" => &[0, 3, 7, 10],
+        "dominant seventh" | "7" => &[0, 4, 7, 10],
+        "major scale" => &[0, 2, 4, 5, 7, 9, 11],
+        "minor scale" => &[0, 2, 3, 5, 7, 8, 10],
+        "pentatonic" | "pentatonic scale" | "major pentatonic" => &[0, 2, 4, 7, 9],
+        "minor pentatonic" => &[0, 3, 5, 7, 10],
+        "whole tone" | "whole tone scale" => &[0, 2, 4, 6, 8, 10],
+        "chromatic" | "chromatic scale" => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        _ => return None,
+    };
+    Some(intervals)
+}
+
+// "c#3", "eb", "a" or "220hz"
+fn parse_root(token: &str) -> Option<f64> {
+    if let Some(hz) = token.strip_suffix("hz") {
+        return hz.parse::<f64>().ok().filter(|f| *f > 0.0);
+    }
+    let mut chars = token.chars();
+    let pitch_class = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = if let Some(r) = rest.strip_prefix('#') {
+        (1, r)
+    } else if let Some(r) = rest.strip_prefix('b') {
+        (-1, r)
+    } else {
+        (0, rest)
+    };
+    let octave = if octave.is_empty() { DEFAULT_OCTAVE } else { octave.parse::<i32>().ok()? };
+    let midi = 12 * (octave + 1) + pitch_class + accidental;
+    Some(440.0 * 2_f64.powf((midi - 69) as f64 / 12.0))
+}
+
+// "<root> [chord or scale] [harmonics <a>-<b>]", e.g. "C major triad harmonics 1-16" or "110hz harmonics 1-32".
+// every chord tone gets the harmonics in the range, harmonic n with amplitude 1/n
+fn parse_spec(spec: &str, sample_rate: f64) -> Result<Vec<(f64, f64, Option<f64>)>, Box<dyn Error>> {
+    let spec = spec.to_lowercase().replace(['\u{2013}', '\u{2014}'], "-");
+    let mut tokens = spec.split_whitespace();
+    let root_token = tokens.next().ok_or("Empty plan spec")?;
+    let root = parse_root(root_token).ok_or_else(|| format!("Expected a note like C4 or a frequency like 220hz, got '{}'", root_token))?;
+
+    let rest = tokens.collect::<Vec<_>>();
+    let (chord, harmonics) = match rest.iter().position(|t| *t == "harmonics" || *t == "harmonic") {
+        Some(i) => (rest[..i].join(" "), Some(rest[i + 1..].join("").replace("to", "-"))),
+        None => (rest.join(" "), None),
+    };
+    let intervals = intervals(&chord).ok_or_else(|| format!("Unknown chord or scale '{}'", chord))?;
+    let (first, last) = match harmonics {
+        Some(range) => {
+            let parse = |v: &str| v.parse::<u32>().map_err(|_| format!("Invalid harmonic range '{}'", range));
+            match range.split_once('-') {
+                Some((a, b)) => (parse(a)?, parse(b)?),
+                None => (parse(&range)?, parse(&range)?),
+            }
+        },
+        None => (1, 1),
+    };
+    if first == 0 || last < first {
+        return Err(format!("Invalid harmonic range {}-{}", first, last).into());
+    }
+
+    let max_freq = sample_rate / 2.0 * MAX_NYQUIST_FRACTION;
+    let mut resonators = Vec::new();
+    for interval in intervals {
+        let freq = root * 2_f64.powf(*interval as f64 / 12.0);
+        for n in first..=last {
+            if freq * n as f64 >= max_freq {
+                break;
+            }
+            resonators.push((freq * n as f64, 1.0 / n as f64, None));
+        }
+    }
+    Ok(resonators)
+}
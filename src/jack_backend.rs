@@ -0,0 +1,210 @@
+use jack::{AsyncClient, AudioIn, AudioOut, Client, ClientOptions, Control, Port, ProcessHandler, ProcessScope, TransportState};
+use parking_lot::Mutex;
+use rtrb::{Producer, RingBuffer};
+use std::error::Error;
+use std::sync::Arc;
+use crate::level::LevelMeter;
+use crate::state::AudioState;
+use crate::stream::{self, StreamTaps, ANALYZER_BUFFER_SIZE};
+
+// the stereo ports of a track, registered as <client>:<track>_in_l, <track>_out_r and so on
+struct TrackPorts {
+    input: [Port<AudioIn>; 2],
+    output: [Port<AudioOut>; 2],
+}
+
+impl TrackPorts {
+    fn register(client: &Client, track: &str) -> Result<Self, jack::Error> {
+        Ok(Self {
+            input: [
+                client.register_port(&format!("{}_in_l", track), AudioIn::default())?,
+                client.register_port(&format!("{}_in_r", track), AudioIn::default())?,
+            ],
+            output: [
+                client.register_port(&format!("{}_out_l", track), AudioOut::default())?,
+                client.register_port(&format!("{}_out_r", track), AudioOut::default())?,
+            ],
+        })
+    }
+
+    // the input as interleaved stereo
+    #[inline]
+    fn read_input(&self, ps: &ProcessScope, buf: &mut [f32]) {
+        let (l, r) = (self.input[0].as_slice(ps), self.input[1].as_slice(ps));
+        for i in 0..buf.len() / 2 {
+            buf[2 * i] = l[i];
+            buf[2 * i + 1] = r[i];
+        }
+    }
+
+    #[inline]
+    fn write_output(&mut self, ps: &ProcessScope, buf: &[f32]) {
+        let [l, r] = &mut self.output;
+        let (l, r) = (l.as_mut_slice(ps), r.as_mut_slice(ps));
+        for i in 0..buf.len() / 2 {
+            l[i] = buf[2 * i];
+            r[i] = buf[2 * i + 1];
+        }
+    }
+}
+
+// keeps the tracks and the jack transport in step. jack starting or stopping starts or stops the
+// source track, the ui starting or stopping the source track starts or stops jack, and locating
+// jack moves both tracks. the resonant track is started on its own like in the druid ui
+struct TransportSync {
+    // the jack transport state in the last cycle
+    rolling: bool,
+    // whether the source track was playing after the last cycle
+    playing: bool,
+    // where jack should be in this cycle if nobody located it
+    next_frame: u32,
+}
+
+struct Process {
+    audio: Arc<Mutex<AudioState>>,
+    r_audio: Arc<Mutex<AudioState>>,
+    // source, resonant and master
+    ports: [TrackPorts; 3],
+    sync: TransportSync,
+    sample_rate: f64,
+    // interleaved scratch buffers, grown when jack's buffer size grows
+    input: Vec<f32>,
+    track: Vec<f32>,
+    master: Vec<f32>,
+    analyzer: Producer<f32>,
+    master_meter: Arc<LevelMeter>,
+}
+
+impl Process {
+    fn sync_transport(&mut self, client: &Client, frames: u32) {
+        let transport = client.transport();
+        let status = match transport.query() {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let rolling = status.state == TransportState::Rolling;
+        let frame = status.pos.frame();
+        let relocated = frame != self.sync.next_frame;
+        let mut audio = self.audio.lock();
+        if rolling != self.sync.rolling {
+            audio.set_playing(rolling);
+        } else if audio.playing != self.sync.playing {
+            let _ = if audio.playing { transport.start() } else { transport.stop() };
+        }
+        if relocated {
+            locate(&mut audio, frame, self.sample_rate);
+        }
+        self.sync.playing = audio.playing;
+        // one track locked at a time like in the cpal callback
+        std::mem::drop(audio);
+        if relocated {
+            locate(&mut self.r_audio.lock(), frame, self.sample_rate);
+        }
+        self.sync.rolling = rolling;
+        self.sync.next_frame = if rolling { frame.wrapping_add(frames) } else { frame };
+    }
+}
+
+// moves a track to a jack frame, wrapping around the file when it loops. the file is played at its
+// own sample rate like in the cpal stream, so the position is converted
+fn locate(audio: &mut AudioState, frame: u32, jack_sample_rate: f64) {
+    let length = audio.samples()[0].len();
+    let loc = (frame as f64 / jack_sample_rate * audio.sample_rate) as usize;
+    audio.set_loc_samples(if audio.looping && length > 0 { loc % length } else { loc });
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, client: &Client, ps: &ProcessScope) -> Control {
+        let frames = ps.n_frames();
+        self.sync_transport(client, frames);
+        let len = 2 * frames as usize;
+        for buf in [&mut self.input, &mut self.track, &mut self.master] {
+            buf.resize(len, 0.0);
+        }
+
+        self.master.iter_mut().for_each(|v| *v = 0.0);
+        for (t, audio) in [&self.audio, &self.r_audio].into_iter().enumerate() {
+            self.ports[t].read_input(ps, &mut self.input);
+            self.track.iter_mut().for_each(|v| *v = 0.0);
+            // the input is heard while the file is stopped, without it the track is silent as in the cpal stream
+            let has_input = self.input.iter().any(|v| *v != 0.0);
+            let mut audio_state = audio.lock();
+            if audio_state.playing || has_input {
+                audio_state.add_audio_with_input(&mut self.track, if has_input { Some(&self.input[..]) } else { None });
+            } else {
+                audio_state.meter.clear();
+            }
+            std::mem::drop(audio_state);
+            self.ports[t].write_output(ps, &self.track);
+            for (m, v) in self.master.iter_mut().zip(self.track.iter()) {
+                *m += v;
+            }
+        }
+        self.ports[2].read_input(ps, &mut self.input);
+        for (m, v) in self.master.iter_mut().zip(self.input.iter()) {
+            *m += v;
+        }
+
+        // the same as the end of the cpal callback
+        self.master_meter.update(&self.master);
+        for frame in self.master.chunks(2) {
+            let _ = self.analyzer.push((frame[0] + frame[1]) / 2.0);
+        }
+        for v in self.master.iter_mut() {
+            *v = v.max(-1.0).min(1.0);
+        }
+        stream::write_record_tap(&self.master);
+        self.ports[2].write_output(ps, &self.master);
+        Control::Continue
+    }
+}
+
+// the running jack client, deactivated when dropped
+pub struct JackBackend {
+    _client: AsyncClient<(), Process>,
+}
+
+// plays the tracks through a jack client instead of the default cpal host. the ports aren't
+// connected to anything, patch them with the session manager or jack_connect
+pub fn start_jack(client_name: &str, audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<AudioState>>) -> Result<(JackBackend, StreamTaps), Box<dyn Error>> {
+    let (client, _status) = Client::new(client_name, ClientOptions::NO_START_SERVER)
+        .map_err(|e| format!("Error while connecting to the jack server: {:?}", e))?;
+    let ports = [
+        TrackPorts::register(&client, "source")?,
+        TrackPorts::register(&client, "resonant")?,
+        TrackPorts::register(&client, "master")?,
+    ];
+    let sample_rate = client.sample_rate() as f64;
+    let buffer_len = 2 * client.buffer_size() as usize;
+
+    let status = client.transport().query()?;
+    let sync = TransportSync {
+        rolling: status.state == TransportState::Rolling,
+        playing: audio.lock().playing,
+        next_frame: status.pos.frame(),
+    };
+    let (analyzer, analyzer_consumer) = RingBuffer::new(ANALYZER_BUFFER_SIZE);
+    let master_meter = Arc::new(LevelMeter::new());
+    let process = Process {
+        audio,
+        r_audio,
+        ports,
+        sync,
+        sample_rate,
+        input: vec![0.0; buffer_len],
+        track: vec![0.0; buffer_len],
+        master: vec![0.0; buffer_len],
+        analyzer,
+        master_meter: Arc::clone(&master_meter),
+    };
+    let client = client.activate_async((), process)
+        .map_err(|e| format!("Error while activating the jack client: {:?}", e))?;
+
+    let taps = StreamTaps {
+        analyzer: analyzer_consumer,
+        output_sample_rate: sample_rate,
+        output_channels: 2,
+        master_meter,
+    };
+    Ok((JackBackend { _client: client }, taps))
+}
@@ -0,0 +1,74 @@
+// runs the jack backend against a running server, start one without a sound card with
+//     jackd -d dummy -r 48000 -p 512
+// and run cargo test --features jack -- --ignored
+use jack::{Client, ClientOptions, PortFlags, TransportState};
+use std::time::Duration;
+use crate::engine::{Engine, Track};
+use crate::state::AudioState;
+
+const SAMPLE_RATE: f64 = 48000.0;
+// a few dummy server cycles
+const WAIT: Duration = Duration::from_millis(300);
+
+fn test_engine() -> Engine {
+    let noise = (0..SAMPLE_RATE as usize * 2).map(|i| ((i * 7919) % 200) as f32 / 100.0 - 1.0).collect::<Vec<f32>>();
+    let source = AudioState::from_samples("source".to_string(), [noise.clone(), noise.clone()], SAMPLE_RATE);
+    let resonant = AudioState::from_samples("resonant".to_string(), [noise.clone(), noise], SAMPLE_RATE);
+    Engine::new(source, resonant)
+}
+
+fn probe(name: &str) -> Client {
+    Client::new(name, ClientOptions::NO_START_SERVER).expect("no jack server running").0
+}
+
+#[test]
+#[ignore]
+fn registers_named_ports() {
+    let mut engine = test_engine();
+    engine.start_jack("capstone-ports").unwrap();
+    let probe = probe("capstone-ports-probe");
+    let ports = probe.ports(Some("capstone-ports:"), None, PortFlags::empty());
+    for track in ["source", "resonant", "master"] {
+        for port in ["in_l", "in_r", "out_l", "out_r"] {
+            let name = format!("capstone-ports:{}_{}", track, port);
+            assert!(ports.contains(&name), "missing port {}", name);
+        }
+    }
+}
+
+#[test]
+#[ignore]
+fn follows_jack_transport() {
+    let mut engine = test_engine();
+    engine.start_jack("capstone-transport").unwrap();
+    let transport = probe("capstone-transport-probe").transport();
+    transport.stop().unwrap();
+    transport.locate(0).unwrap();
+    std::thread::sleep(WAIT);
+    assert!(!engine.source().lock().playing);
+
+    transport.start().unwrap();
+    std::thread::sleep(WAIT);
+    {
+        let audio_state = engine.source().lock();
+        assert!(audio_state.playing);
+        assert!(audio_state.get_loc() > 0);
+    }
+
+    transport.stop().unwrap();
+    std::thread::sleep(WAIT);
+    assert!(!engine.source().lock().playing);
+
+    // a second into the files
+    transport.locate(SAMPLE_RATE as u32).unwrap();
+    std::thread::sleep(WAIT);
+    for track in [engine.source(), engine.resonant()] {
+        assert_eq!(track.lock().get_loc(), SAMPLE_RATE as usize);
+    }
+
+    // playing from the engine starts jack
+    engine.set_playing(Track::Source, true);
+    std::thread::sleep(WAIT);
+    assert!(transport.query().unwrap().state == TransportState::Rolling);
+    transport.stop().unwrap();
+}
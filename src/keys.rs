@@ -0,0 +1,385 @@
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use druid::{Data, KbKey, KeyEvent, Selector};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use crate::AppState;
+use crate::automation::AutomationParam;
+use crate::graph::GraphData;
+
+pub const DEFAULT_KEYS_PATH: &str = "./keys.toml";
+// how far the seek keys move the source playhead, in seconds
+const SEEK_STEP: f64 = 1.0;
+// how far the nudge keys move the focused slider, as a fraction of its range
+const NUDGE_STEP: f64 = 0.01;
+
+// the sliders that can be nudged from the keyboard. a slider is focused by clicking it
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub enum SliderParam {
+    MaxPeaks,
+    MinProminence,
+    MinThreshold,
+    MinFreq,
+    MaxFreq,
+    Decay,
+    Volume,
+    Transpose,
+    Morph,
+    SidechainAttack,
+    SidechainRelease,
+    SidechainDepth,
+    DecayTilt,
+    GainTilt,
+}
+
+impl SliderParam {
+    // the range of the slider, matching build_ui
+    fn range(&self) -> (f64, f64) {
+        match self {
+            SliderParam::Volume => (-40.0, 6.0),
+            SliderParam::Transpose => (-1.0, 1.0),
+            SliderParam::SidechainAttack => (0.001, 0.5),
+            SliderParam::SidechainRelease => (0.01, 2.0),
+            SliderParam::DecayTilt => (0.25, 1.5),
+            SliderParam::GainTilt => (-12.0, 6.0),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    fn is_planner(&self) -> bool {
+        matches!(self, SliderParam::MaxPeaks | SliderParam::MinProminence | SliderParam::MinThreshold | SliderParam::MinFreq | SliderParam::MaxFreq)
+    }
+
+    fn graph_field<'a>(&self, graph: &'a mut GraphData) -> Option<&'a mut f64> {
+        match self {
+            SliderParam::MaxPeaks => Some(&mut graph.planner.max_peaks),
+            SliderParam::MinProminence => Some(&mut graph.planner.min_prominence),
+            SliderParam::MinThreshold => Some(&mut graph.planner.min_line),
+            SliderParam::MinFreq => Some(&mut graph.planner.min_range),
+            SliderParam::MaxFreq => Some(&mut graph.planner.max_range),
+            _ => None,
+        }
+    }
+
+    // moves the value by steps of NUDGE_STEP, the same way dragging the slider would
+    fn nudge(&self, data: &mut AppState, steps: f64) {
+        let (min, max) = self.range();
+        let delta = steps * NUDGE_STEP * (max - min);
+        if self.is_planner() {
+            let before = data.line_graph.planner.clone();
+            if let Some(v) = self.graph_field(&mut data.line_graph) {
+                *v = (*v + delta).max(min).min(max);
+            }
+            if before != data.line_graph.planner {
+                data.history.lock().push_planner(before);
+            }
+            return;
+        }
+        let mut audio_state = data.audio_state.lock();
+        let (value, param) = match self {
+            SliderParam::Decay => (&mut audio_state.params.decay, Some(AutomationParam::Decay)),
+            SliderParam::Volume => (&mut audio_state.params.volume, Some(AutomationParam::Volume)),
+            SliderParam::Transpose => (&mut audio_state.params.transpose, Some(AutomationParam::Transpose)),
+            SliderParam::SidechainAttack => (&mut audio_state.params.sidechain.attack, None),
+            SliderParam::SidechainRelease => (&mut audio_state.params.sidechain.release, None),
+            SliderParam::SidechainDepth => (&mut audio_state.params.sidechain.depth, None),
+            SliderParam::DecayTilt => (&mut audio_state.params.shaping.decay_tilt, None),
+            SliderParam::GainTilt => (&mut audio_state.params.shaping.gain_tilt, None),
+            _ => (&mut audio_state.params.morph_pos, Some(AutomationParam::Morph)),
+        };
+        *value = (*value + delta).max(min).min(max);
+        if let Some(param) = param {
+            audio_state.record_automation(param);
+        }
+        if matches!(self, SliderParam::DecayTilt | SliderParam::GainTilt) {
+            audio_state.apply_shaping();
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    PlaySource,
+    PlayResonant,
+    Build,
+    SeekBack,
+    SeekForward,
+    NudgeUp,
+    NudgeDown,
+    // jump the source playhead to n tenths of the file
+    Jump(u8),
+    Undo,
+    Redo,
+}
+
+impl Action {
+    // the name used in the config file
+    fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "play_source" => Action::PlaySource,
+            "play_resonant" => Action::PlayResonant,
+            "build" => Action::Build,
+            "seek_back" => Action::SeekBack,
+            "seek_forward" => Action::SeekForward,
+            "nudge_up" => Action::NudgeUp,
+            "nudge_down" => Action::NudgeDown,
+            "undo" => Action::Undo,
+            "redo" => Action::Redo,
+            _ => {
+                let n = name.strip_prefix("jump_")?.parse::<u8>().ok().filter(|n| *n < 10)?;
+                Action::Jump(n)
+            }
+        };
+        Some(action)
+    }
+}
+
+// a key with the modifiers that have to be held, parsed from e.g. "Ctrl+Shift+Z"
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyCombo {
+    key: KbKey,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    meta: bool,
+}
+
+impl KeyCombo {
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut combo = KeyCombo {
+            key: KbKey::Unidentified,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            meta: false,
+        };
+        let mut parts = s.split('+').map(|p| p.trim()).collect::<Vec<_>>();
+        let key = parts.pop().filter(|k| !k.is_empty()).ok_or_else(|| format!("Missing key in '{}'", s))?;
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "shift" => combo.shift = true,
+                "alt" | "option" => combo.alt = true,
+                "meta" | "cmd" | "super" => combo.meta = true,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", modifier, s).into()),
+            }
+        }
+        combo.key = if key.eq_ignore_ascii_case("space") {
+            KbKey::Character(" ".to_string())
+        } else if key.chars().count() == 1 {
+            KbKey::Character(key.to_lowercase())
+        } else {
+            match key.parse::<KbKey>() {
+                Ok(KbKey::Character(_)) | Err(_) => return Err(format!("Unknown key '{}' in '{}'", key, s).into()),
+                Ok(v) => v,
+            }
+        };
+        Ok(combo)
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        let key_matches = match (&self.key, &event.key) {
+            (KbKey::Character(a), KbKey::Character(b)) => *a == b.to_lowercase(),
+            (a, b) => a == b,
+        };
+        key_matches
+            && self.ctrl == event.mods.ctrl()
+            && self.shift == event.mods.shift()
+            && self.alt == event.mods.alt()
+            && self.meta == event.mods.meta()
+    }
+}
+
+// one key or a list of keys for an action
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Default)]
+struct KeyConfig {
+    #[serde(default)]
+    bindings: HashMap<String, KeyList>,
+}
+
+pub struct KeyBindings {
+    bindings: Vec<(KeyCombo, Action)>,
+}
+
+impl KeyBindings {
+    fn default_bindings() -> HashMap<String, Vec<String>> {
+        let mut bindings = HashMap::new();
+        let defaults: [(&str, &[&str]); 9] = [
+            ("play_source", &["Space"]),
+            ("play_resonant", &["Shift+Space"]),
+            ("build", &["B"]),
+            ("seek_back", &["ArrowLeft"]),
+            ("seek_forward", &["ArrowRight"]),
+            ("nudge_up", &["ArrowUp"]),
+            ("nudge_down", &["ArrowDown"]),
+            ("undo", &["Ctrl+Z", "Meta+Z"]),
+            ("redo", &["Ctrl+Shift+Z", "Ctrl+Y", "Meta+Shift+Z"]),
+        ];
+        for (name, keys) in defaults {
+            bindings.insert(name.to_string(), keys.iter().map(|k| k.to_string()).collect());
+        }
+        for n in 0..10 {
+            bindings.insert(format!("jump_{}", n), vec![n.to_string()]);
+        }
+        bindings
+    }
+
+    // the default bindings with the ones in the [bindings] table of the file replacing them,
+    // a missing file just gives the defaults. for example:
+    // [bindings]
+    // play_source = "P"
+    // redo = ["Ctrl+Shift+Z", "Ctrl+Y"]
+    // jump_5 = "Alt+5"
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let config: KeyConfig = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => KeyConfig::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Self::default_bindings();
+        for (name, keys) in config.bindings {
+            let keys = match keys {
+                KeyList::One(v) => vec![v],
+                KeyList::Many(v) => v,
+            };
+            names.insert(name, keys);
+        }
+
+        let mut bindings = Vec::new();
+        for (name, keys) in names {
+            let action = Action::from_name(&name).ok_or_else(|| format!("Unknown action '{}' in key bindings", name))?;
+            for key in keys {
+                bindings.push((KeyCombo::parse(&key)?, action));
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.iter().find(|(combo, _)| combo.matches(event)).map(|(_, action)| *action)
+    }
+}
+
+// handles key bindings for the whole window. sits at the root of build_ui and keeps the keyboard focus
+pub struct KeyController {
+    bindings: KeyBindings,
+}
+
+impl KeyController {
+    pub fn new(bindings: KeyBindings) -> Self {
+        Self {
+            bindings,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for KeyController {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        match event {
+            // key events only reach the focused widget
+            Event::WindowConnected => {
+                ctx.request_focus();
+            },
+            Event::Notification(notification) if notification.is(RETURN_FOCUS) => {
+                ctx.request_focus();
+                ctx.set_handled();
+                return;
+            },
+            Event::KeyDown(key_event) if ctx.is_focused() => {
+                if let Some(action) = self.bindings.action_for(key_event) {
+                    run_action(action, data);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                    return;
+                }
+            },
+            _ => {},
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn run_action(action: Action, data: &mut AppState) {
+    match action {
+        Action::PlaySource => {
+            data.playing = !data.playing;
+            data.audio_state.lock().set_playing(data.playing);
+        },
+        Action::PlayResonant => {
+            data.r_playing = !data.r_playing;
+            data.r_audio_state.lock().set_playing(data.r_playing);
+        },
+        Action::Build => crate::build_resonator(data),
+        Action::SeekBack => data.audio_state.lock().seek_by(-SEEK_STEP),
+        Action::SeekForward => data.audio_state.lock().seek_by(SEEK_STEP),
+        Action::NudgeUp | Action::NudgeDown => {
+            if let Some(param) = data.focused_slider {
+                let steps = if action == Action::NudgeUp { 1.0 } else { -1.0 };
+                param.nudge(data, steps);
+            }
+        },
+        Action::Jump(n) => data.audio_state.lock().set_loc(n as f64 / 10.0),
+        Action::Undo => {
+            data.history.lock().undo(&mut data.line_graph, &data.audio_state);
+        },
+        Action::Redo => {
+            data.history.lock().redo(&mut data.line_graph, &data.audio_state);
+        },
+    }
+}
+
+// sent by ImportBoxController when the import box is done with the keyboard
+const RETURN_FOCUS: Selector = Selector::new("capstone-audio-demo.return-focus");
+
+// enter imports what was typed into the import box and escape leaves it, both hand the
+// keyboard back to the shortcuts while the box keeps the text
+pub struct ImportBoxController;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for ImportBoxController {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::KeyDown(key_event) = event {
+            if key_event.key == KbKey::Enter || key_event.key == KbKey::Escape {
+                if key_event.key == KbKey::Enter {
+                    crate::import_from_text(data);
+                }
+                ctx.submit_notification(RETURN_FOCUS);
+                ctx.set_handled();
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
+// makes the wrapped slider the one the nudge keys move when it's clicked
+pub struct SliderFocus {
+    param: SliderParam,
+}
+
+impl SliderFocus {
+    pub fn new(param: SliderParam) -> Self {
+        Self {
+            param,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for SliderFocus {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::MouseDown(_) = event {
+            data.focused_slider = Some(self.param);
+        }
+        child.event(ctx, event, data, env)
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+// peak and rms levels written by the audio thread and read by the ui without locking
+pub struct LevelMeter {
+    // f32 bits, the highest peak since the ui last read it
+    peak: AtomicU32,
+    // f32 bits, rms of the last block
+    rms: AtomicU32,
+    // set when a sample went past full scale, cleared by the ui
+    clipped: AtomicBool,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self {
+            peak: AtomicU32::new(0),
+            rms: AtomicU32::new(0),
+            clipped: AtomicBool::new(false),
+        }
+    }
+
+    // called from the audio thread with one block of (interleaved) samples
+    #[inline]
+    pub fn update(&self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        let mut peak = 0.0_f32;
+        let mut sum_sq = 0.0;
+        for v in samples {
+            peak = peak.max(v.abs());
+            sum_sq += v * v;
+        }
+        // f32 bits of non negative numbers compare the same as the numbers
+        self.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        self.rms.store((sum_sq / samples.len() as f32).sqrt().to_bits(), Ordering::Relaxed);
+        if peak > 1.0 {
+            self.clipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // returns the highest peak since the last call
+    #[inline]
+    pub fn take_peak(&self) -> f32 {
+        f32::from_bits(self.peak.swap(0, Ordering::Relaxed))
+    }
+
+    // called for a track that isn't played in a block, so the rms doesn't hold the level of the
+    // last block it played. the sidechain of the other track reads it, see sidechain::Sidechain
+    #[inline]
+    pub fn clear(&self) {
+        self.rms.store(0, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn rms(&self) -> f32 {
+        f32::from_bits(self.rms.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn clipped(&self) -> bool {
+        self.clipped.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn reset_clip(&self) {
+        self.clipped.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,23 @@
+// the audio engine of the demo: loading tracks, planning resonators from the resonant file, running the
+// source through them and playing the result. nothing here depends on the gui, see engine::Engine
+pub mod analysis;
+pub mod audio;
+pub mod automation;
+pub mod engine;
+pub mod export;
+pub mod import;
+#[cfg(feature = "jack")]
+pub mod jack_backend;
+pub mod level;
+pub mod modulation;
+pub mod morph;
+pub mod planner;
+pub mod recorder;
+pub mod shaping;
+pub mod sidechain;
+pub mod state;
+pub mod stream;
+#[cfg(all(test, feature = "jack"))]
+mod jack_tests;
+
+pub use engine::{Engine, Track};
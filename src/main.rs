@@ -1,24 +1,48 @@
-use druid::widget::{Flex, Label, Painter, SizedBox, Slider, Axis};
-use druid::{AppLauncher, Color, Data, Lens, RenderContext, WidgetExt, WindowDesc, Widget, MouseButton, LensExt};
-use druid::kurbo::Rect;
+use druid::widget::{Flex, Label, Painter, SizedBox, Slider, Axis, TextBox};
+use druid::{AppLauncher, Color, Data, Lens, RenderContext, WidgetExt, WindowDesc, Widget, LensExt};
 use graph::{LineGraph, GraphData};
-use resonator_builder::fft::window::WindowFunction;
+use progress::{ProgressBar, CustomProgressBar};
+use transport::TransportController;
+use spectrogram::Spectrogram;
 use std::error::Error;
 use std::path::Path;
 use std::sync::Arc;
 use parking_lot::Mutex;
-use std::env;
-use std::fs::File;
-use std::io::BufReader;
-use rodio::{Decoder, source::Source};
+use capstone_audio_demo::{analysis, audio, automation, export, import, level, modulation, morph, planner, recorder, shaping, sidechain, state, stream};
+use capstone_audio_demo::Engine;
 use state::AudioState;
+use morph::MorphPlan;
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
 use lazy_static::lazy_static;
-use crate::stream::prepare_cpal_stream;
-use resonator_builder::fft::FftCalculator;
+use crate::stream::StreamTaps;
+use level::LevelMeter;
+use meter::MeterView;
+use analyzer::OutputAnalyzer;
+use args::Args;
+use planner::PlannerSettings;
+use recorder::{Recorder, RecordFormat};
+use automation::{Automation, AutomationMode, AutomationParam};
+use history::{History, HistoryController, BuildSnapshot};
+use keys::{ImportBoxController, KeyBindings, KeyController, SliderFocus, SliderParam};
+use session::{Session, WindowLayout};
+use export::ExportFormat;
+use import::{import_plan, ImportedPlan};
+use sidechain::{SidechainKey, SidechainSettings};
+use shaping::ShapingSettings;
 
-mod stream;
-mod state;
 mod graph;
+mod spectrogram;
+mod args;
+mod midi;
+mod osc;
+mod progress;
+mod transport;
+mod meter;
+mod analyzer;
+mod history;
+mod keys;
+mod session;
+mod batch;
 
 lazy_static!{
     static ref DECAY: Mutex<(bool, f64)> = Mutex::new((false, 0.5));
@@ -33,24 +57,64 @@ struct AppState {
     r_progress: ProgressBar,
     playing: bool,
     r_playing: bool,
+    looping: bool,
+    r_looping: bool,
+    // playback position as mm:ss.ms, refreshed by TransportController
+    time: String,
+    r_time: String,
     #[data(ignore)]
     audio_state: Arc<Mutex<AudioState>>,
     #[data(ignore)]
     r_audio_state: Arc<Mutex<AudioState>>,
     line_graph: GraphData,
+    // plans stored for morphing, one plan per channel
+    #[data(ignore)]
+    morph_slots: Arc<Mutex<[Option<(ScaledResonatorPlan, ScaledResonatorPlan)>; 2]>>,
+    // number of resonators in each morph slot, for display
+    morph_a_peaks: usize,
+    morph_b_peaks: usize,
+    recording: bool,
+    #[data(same_fn = "PartialEq::eq")]
+    record_format: RecordFormat,
+    #[data(ignore)]
+    recorder: Arc<Mutex<Recorder>>,
+    #[data(same_fn = "PartialEq::eq")]
+    automation_mode: AutomationMode,
+    // where the automation lanes are saved and loaded from
+    automation_path: String,
+    // undo and redo of planner edits and builds
+    #[data(ignore)]
+    history: Arc<Mutex<History>>,
+    // the slider the arrow keys move, the last one clicked
+    focused_slider: Option<SliderParam>,
+    // where SAVE SESSION writes to
+    session_path: String,
+    #[data(same_fn = "PartialEq::eq")]
+    export_format: ExportFormat,
+    // file path or spec for IMPORT PLAN, see import::import_plan
+    import_text: String,
+    // mirrors the source track's sidechain settings for the buttons
+    #[data(same_fn = "PartialEq::eq")]
+    sidechain_key: SidechainKey,
+    sidechain_invert: bool,
 }
 
 struct AudioDecayLens;
 
 impl Lens<AppState, f64> for AudioDecayLens {
     fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
-        let decay = data.audio_state.lock().decay;
+        let decay = data.audio_state.lock().params.decay;
         f(&decay)
     }
 
     fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
         let mut audio_state = data.audio_state.lock();
-        f(&mut audio_state.decay)
+        let old = audio_state.params.decay;
+        let v = f(&mut audio_state.params.decay);
+        if audio_state.params.decay != old {
+            audio_state.record_automation(AutomationParam::Decay);
+        }
+        v
     }
 }
 
@@ -58,13 +122,18 @@ struct AudioVolumeLens;
 
 impl Lens<AppState, f64> for AudioVolumeLens {
     fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
-        let volume = data.audio_state.lock().volume;
+        let volume = data.audio_state.lock().params.volume;
         f(&volume)
     }
 
     fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
         let mut audio_state = data.audio_state.lock();
-        f(&mut audio_state.volume)
+        let old = audio_state.params.volume;
+        let v = f(&mut audio_state.params.volume);
+        if audio_state.params.volume != old {
+            audio_state.record_automation(AutomationParam::Volume);
+        }
+        v
     }
 }
 
@@ -72,123 +141,208 @@ struct AudioTransposeLens;
 
 impl Lens<AppState, f64> for AudioTransposeLens {
     fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
-        let transpose = data.audio_state.lock().transpose;
+        let transpose = data.audio_state.lock().params.transpose;
         f(&transpose)
     }
 
     fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
         let mut audio_state = data.audio_state.lock();
-        f(&mut audio_state.transpose)
+        let old = audio_state.params.transpose;
+        let v = f(&mut audio_state.params.transpose);
+        if audio_state.params.transpose != old {
+            audio_state.record_automation(AutomationParam::Transpose);
+        }
+        v
     }
 }
 
-#[derive(Clone)]
-struct ProgressBar {
-    audio: Arc<Mutex<AudioState>>,
-}
+// a field of the source track's sidechain settings
+struct SidechainLens(fn(&mut SidechainSettings) -> &mut f64);
 
-impl Data for ProgressBar {
-    fn same(&self, _other: &Self) -> bool {
-        // stub fow now
-        false
+impl Lens<AppState, f64> for SidechainLens {
+    fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
+        let mut settings = data.audio_state.lock().params.sidechain;
+        f((self.0)(&mut settings))
     }
-}
 
-impl ProgressBar {
-    pub fn init(audio: Arc<Mutex<AudioState>>) -> Self {
-        Self {
-            audio,
-        }
+    fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
+        let mut audio_state = data.audio_state.lock();
+        f((self.0)(&mut audio_state.params.sidechain))
     }
 }
 
-struct CustomProgressBar;
-
-impl Widget<ProgressBar> for CustomProgressBar {
-    fn event(&mut self, ctx: &mut druid::EventCtx, event: &druid::Event, data: &mut ProgressBar, _env: &druid::Env) {
-        
-        match event {
-            druid::Event::MouseDown(mouse_event) => {
-                if mouse_event.button == MouseButton::Left {
-                    let x = mouse_event.pos.x;
-                    let width = ctx.size().width;
-                    let mut audio = data.audio.lock();
-                    let progress = (x / width).max(0.0).min(1.0);
-                    audio.set_loc(progress);
-                    std::mem::drop(audio);
-                    ctx.set_handled();
-                }
-            },
-            druid::Event::WindowConnected => {
-                ctx.request_timer(std::time::Duration::from_secs_f64(1.0 / 60.0));
-            }
-            druid::Event::Timer(_) => {
-                ctx.request_paint();
-                ctx.request_timer(std::time::Duration::from_secs_f64(1.0 / 60.0));
-            }
-            _ => {}
+// a tilt of the source track's resonator shaping
+struct ShapingLens(fn(&mut ShapingSettings) -> &mut f64);
+
+impl Lens<AppState, f64> for ShapingLens {
+    fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
+        let mut audio_state = data.audio_state.lock();
+        let value = *(self.0)(&mut audio_state.params.shaping);
+        std::mem::drop(audio_state);
+        f(&value)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
+        let mut audio_state = data.audio_state.lock();
+        let before = *(self.0)(&mut audio_state.params.shaping);
+        let result = f((self.0)(&mut audio_state.params.shaping));
+        if *(self.0)(&mut audio_state.params.shaping) != before {
+            audio_state.apply_shaping();
         }
+        result
     }
+}
 
-    fn lifecycle(&mut self, _ctx: &mut druid::LifeCycleCtx, _event: &druid::LifeCycle, _data: &ProgressBar, _env: &druid::Env) {}
+struct AudioMorphLens;
 
-    fn update(&mut self, ctx: &mut druid::UpdateCtx, old_data: &ProgressBar, data: &ProgressBar, _env: &druid::Env) {
-        if !old_data.same(data) {
-            ctx.request_paint();
-        }
+impl Lens<AppState, f64> for AudioMorphLens {
+    fn with<V, F: FnOnce(&f64) -> V>(&self, data: &AppState, f: F) -> V {
+        let morph_pos = data.audio_state.lock().params.morph_pos;
+        f(&morph_pos)
     }
 
-    fn layout(&mut self, _ctx: &mut druid::LayoutCtx, bc: &druid::BoxConstraints, _data: &ProgressBar, _env: &druid::Env) -> druid::Size {
-        bc.max()
+    fn with_mut<V, F: FnOnce(&mut f64) -> V>(&self, data: &mut AppState, f: F) -> V {
+        let mut audio_state = data.audio_state.lock();
+        let old = audio_state.params.morph_pos;
+        let v = f(&mut audio_state.params.morph_pos);
+        if audio_state.params.morph_pos != old {
+            audio_state.record_automation(AutomationParam::Morph);
+        }
+        v
     }
+}
 
-    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &ProgressBar, _env: &druid::Env) {
-        let size = ctx.size();
-        let rect = Rect::from_origin_size((0.0, 0.0), size);
-        let audio = data.audio.lock();
-        let progress = audio.get_progress();
-        let filled_rect = Rect::from_origin_size((0.0, 0.0), (size.width * progress, size.height));
-        std::mem::drop(audio);
-        ctx.fill(rect, &Color::grey(1.0));
-        ctx.fill(filled_rect, &Color::rgb8(0x7B, 0x61, 0x9E));
+impl AppState {
+    // the app state for a session, with the tracks already loaded from it
+    fn from_session(session: &Session, audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<AudioState>>, recorder: Arc<Mutex<Recorder>>, args: &Args) -> Result<Self, Box<dyn Error>> {
+        // the buttons' copies of the track state, taken from the tracks as loaded
+        let audio_state = audio.lock();
+        let (playing, looping, automation_mode) = (audio_state.playing, audio_state.looping, audio_state.automation.mode());
+        let sidechain = audio_state.params.sidechain;
+        std::mem::drop(audio_state);
+        let r_audio_state = r_audio.lock();
+        let (r_playing, r_looping) = (r_audio_state.playing, r_audio_state.looping);
+        std::mem::drop(r_audio_state);
+        let morph_slots = [
+            session.morph_slots[0].as_ref().map(|saved| saved.plans()),
+            session.morph_slots[1].as_ref().map(|saved| saved.plans()),
+        ];
+        let slot_peaks = |i: usize| morph_slots[i].as_ref().map_or(0, |plans| plans.0.resonators.len());
+        let mut line_graph = GraphData::new(&session.resonant.path, &session.planner)?;
+        if let Some(saved) = &session.imported {
+            line_graph.set_imported(ImportedPlan { plans: saved.plans(), decay: session.imported_decay });
+        }
+        Ok(
+            Self {
+                progress: ProgressBar::init(Arc::clone(&audio)),
+                r_progress: ProgressBar::init(Arc::clone(&r_audio)),
+                playing,
+                r_playing,
+                looping,
+                r_looping,
+                time: String::new(),
+                r_time: String::new(),
+                audio_state: audio,
+                r_audio_state: r_audio,
+                line_graph,
+                morph_a_peaks: slot_peaks(0),
+                morph_b_peaks: slot_peaks(1),
+                morph_slots: Arc::new(Mutex::new(morph_slots)),
+                recording: args.record_path.is_some(),
+                record_format: args.record_format,
+                recorder,
+                automation_mode,
+                automation_path: args.automation_path.clone().unwrap_or_else(|| automation::DEFAULT_AUTOMATION_PATH.to_string()),
+                history: Arc::new(Mutex::new(History::new())),
+                focused_slider: None,
+                session_path: args.session_path.clone().unwrap_or_else(|| session::DEFAULT_SESSION_PATH.to_string()),
+                export_format: ExportFormat::Csv,
+                import_text: String::new(),
+                sidechain_key: sidechain.key,
+                sidechain_invert: sidechain.invert,
+            }
+        )
     }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-    let audio_path = if args.len() > 1 {
-        args[1].as_str()
-    } else {
-        "./audio/poem1b.wav"
+    let mut args = Args::parse()?;
+    let session = match &args.session_path {
+        Some(path) if Path::new(path).exists() => Session::load(path)?,
+        _ => Session::from_args(&args),
     };
 
-    let r_audio_path = if args.len() > 2 {
-        args[2].as_str()
-    } else {
-        "./audio/Datmosphere.wav"
-    };
+    if let Some(config) = &args.batch {
+        return batch::run_batch(config, &session.planner, &session.resonant.path, &session.source.params, args.record_format);
+    }
 
-    let window = WindowDesc::new(build_ui(audio_path.to_string(), r_audio_path.to_string()))
-        .title("Capstone Project Demo");
-    let audio = Arc::new(Mutex::new(AudioState::init_audio_state(audio_path)?));
-    let r_audio = Arc::new(Mutex::new(AudioState::init_audio_state(r_audio_path)?));
+    let mut audio_state = session.source.load_audio_state()?;
+    if let Some(plan) = &session.plan {
+        plan.restore(&mut audio_state)?;
+    }
+    let mut engine = Engine::new(audio_state, session.resonant.load_audio_state()?);
+    engine.set_planner(session.planner.clone());
+    if let Some(path) = &args.modulation_path {
+        engine.set_modulation(modulation::ModulationSettings::load(path)?);
+    }
+    if let Some(path) = &args.shaping_path {
+        engine.set_shaping(ShapingSettings::load(path)?);
+    }
+    let audio = Arc::clone(engine.source());
+    let r_audio = Arc::clone(engine.resonant());
     
-    let stream = prepare_cpal_stream(Arc::clone(&audio), Arc::clone(&r_audio))?;
-    let state = AppState {
-        progress: ProgressBar::init(Arc::clone(&audio)),
-        r_progress: ProgressBar::init(Arc::clone(&r_audio)),
-        playing: false,
-        r_playing: false,
-        audio_state: audio,
-        r_audio_state: r_audio,
-        line_graph: GraphData::new(r_audio_path)?,
+    if let Some(path) = &args.automation_path {
+        if Path::new(path).exists() {
+            let mut audio_state = audio.lock();
+            audio_state.automation = Automation::load(path, audio_state.sample_rate)?;
+            audio_state.automation.set_mode(AutomationMode::Play);
+        }
+    }
+    
+    let taps = match &args.jack {
+        #[cfg(feature = "jack")]
+        Some(name) => engine.start_jack(name)?,
+        _ => engine.start_stream()?,
     };
-    AppLauncher::with_window(window)
-        .launch(state)?;
+    let meters = [Arc::clone(&audio.lock().meter), Arc::clone(&r_audio.lock().meter), Arc::clone(&taps.master_meter)];
+    let recorder = Arc::new(Mutex::new(Recorder::new(
+        session.source.path.clone(),
+        session.resonant.path.clone(),
+        taps.output_channels,
+        taps.output_sample_rate as u32,
+    )));
+    if let Some(path) = &args.record_path {
+        let audio_state = audio.lock();
+        recorder.lock().start(path, args.record_format, audio_state.plan.as_ref(), audio_state.sample_rate)?;
+    }
+    let key_bindings = KeyBindings::load(&args.keys_path)?;
+    let mut window = WindowDesc::new(build_ui(session.source.path.clone(), session.resonant.path.clone(), taps, meters, key_bindings))
+        .title("Capstone Project Demo");
+    if let Some(layout) = &session.window {
+        window = window.window_size(layout.size()).set_position(layout.position());
+    }
+    let _midi = match args.midi.take() {
+        Some(config) => Some(midi::connect_midi(Arc::clone(&audio), config)?),
+        None => None,
+    };
+    let launcher = AppLauncher::with_window(window);
+    let _osc = match args.osc_port {
+        Some(port) => Some(osc::start_osc_server(port, Arc::clone(&audio), Arc::clone(&r_audio), launcher.get_external_handle())?),
+        None => None,
+    };
+    let mut state = AppState::from_session(&session, audio, r_audio, Arc::clone(&recorder), &args)?;
+    if let Some(text) = &args.import {
+        let sample_rate = state.audio_state.lock().sample_rate;
+        state.line_graph.set_imported(import_plan(text, sample_rate)?);
+        state.import_text = text.clone();
+    }
+    launcher.launch(state)?;
+    // finish the file when the window is closed while recording
+    recorder.lock().stop()?;
     Ok(())
 }
 
-fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppState> {
+fn build_ui(audio_text: String, r_audio_text: String, taps: StreamTaps, meters: [Arc<LevelMeter>; 3], key_bindings: KeyBindings) -> impl druid::Widget<AppState> {
     let play_pause_button = Label::new(|data: &AppState, _env: &_| {
         if data.playing {
             "Pause".to_string()
@@ -204,10 +358,42 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
     }))
     .on_click(|_ctx, data: &mut AppState, _env| {
         data.playing = !data.playing;
-        data.audio_state.lock().playing = data.playing;
+        data.audio_state.lock().set_playing(data.playing);
+    });
+
+    let stop_button = Label::new("Stop")
+    .with_text_size(24.0)
+    .padding(10.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.playing = false;
+        data.audio_state.lock().rewind();
     });
 
-    let progress_bar = SizedBox::new(CustomProgressBar.lens(AppState::progress)).height(24.0);
+    let loop_button = Label::new(|data: &AppState, _env: &_| {
+        if data.looping {
+            "Loop: on".to_string()
+        } else {
+            "Loop: off".to_string()
+        }
+    })
+    .with_text_size(24.0)
+    .padding(10.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.looping = !data.looping;
+        data.audio_state.lock().looping = data.looping;
+    });
+
+    let time_label = Label::new(|data: &AppState, _env: &_| data.time.clone());
+
+    let progress_bar = SizedBox::new(CustomProgressBar::new().lens(AppState::progress)).height(48.0);
     let label = Label::new(audio_text);
 
     let r_play_pause_button = Label::new(|data: &AppState, _env: &_| {
@@ -225,13 +411,59 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
     }))
     .on_click(|_ctx, data: &mut AppState, _env| {
         data.r_playing = !data.r_playing;
-        data.r_audio_state.lock().playing = data.r_playing;
+        data.r_audio_state.lock().set_playing(data.r_playing);
+    });
+
+    let r_stop_button = Label::new("Stop")
+    .with_text_size(24.0)
+    .padding(10.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.r_playing = false;
+        data.r_audio_state.lock().rewind();
+    });
+
+    let r_loop_button = Label::new(|data: &AppState, _env: &_| {
+        if data.r_looping {
+            "Loop: on".to_string()
+        } else {
+            "Loop: off".to_string()
+        }
+    })
+    .with_text_size(24.0)
+    .padding(10.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.r_looping = !data.r_looping;
+        data.r_audio_state.lock().looping = data.r_looping;
     });
 
-    let r_progress_bar = SizedBox::new(CustomProgressBar.lens(AppState::r_progress)).height(24.0);
+    let r_time_label = Label::new(|data: &AppState, _env: &_| data.r_time.clone());
+
+    let r_progress_bar = SizedBox::new(CustomProgressBar::new().lens(AppState::r_progress)).height(48.0);
     let r_label = Label::new(r_audio_text);
 
+    let spectrogram = SizedBox::new(Spectrogram::new().lens(AppState::line_graph)).height(120.0);
     let graph = SizedBox::new(LineGraph.lens(AppState::line_graph)).height(400.0);
+    let analyzer = SizedBox::new(OutputAnalyzer::new(taps.analyzer, taps.output_sample_rate).lens(AppState::line_graph)).height(150.0);
+
+    let [source_meter, resonant_meter, master_meter] = meters;
+    let mut meter_row = Flex::row();
+    for (name, meter) in [("src", source_meter), ("res", resonant_meter), ("out", master_meter)] {
+        meter_row = meter_row
+            .with_child(
+                Flex::column()
+                    .with_child(SizedBox::new(MeterView::new(meter)).width(16.0).height(126.0))
+                    .with_child(Label::new(name))
+            )
+            .with_spacer(4.0);
+    }
 
     let build_button = Label::new("BUILD RESONATOR")
     .with_text_size(24.0)
@@ -240,77 +472,368 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
         let bounds = ctx.size().to_rect();
         ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
     }))
+    .on_click(|_ctx, data: &mut AppState, _env| build_resonator(data));
+
+    let store_a_button = Label::new(|data: &AppState, _env: &_| {
+        format!("STORE A ({})", data.morph_a_peaks)
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        let plans = data.line_graph.current_plans();
+        data.morph_a_peaks = plans.0.resonators.len();
+        data.morph_slots.lock()[0] = Some(plans);
+    });
+
+    let store_b_button = Label::new(|data: &AppState, _env: &_| {
+        format!("STORE B ({})", data.morph_b_peaks)
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        let plans = data.line_graph.current_plans();
+        data.morph_b_peaks = plans.0.resonators.len();
+        data.morph_slots.lock()[1] = Some(plans);
+    });
+
+    let build_morph_button = Label::new("BUILD MORPH")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
     .on_click(|_ctx, data: &mut AppState, _env| {
-        let plan = data.line_graph.plan.lock();
+        let slots = data.morph_slots.lock();
+        let (a, b) = match (&slots[0], &slots[1]) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                println!("Store a plan in both A and B before building a morph");
+                return;
+            }
+        };
+        let morphs = (MorphPlan::new(&a.0, &b.0), MorphPlan::new(&a.1, &b.1));
         let mut audio_state = data.audio_state.lock();
-        let array = match plan.build_resonator_array(audio_state.sample_rate) {
-            Ok(v) => {
-                let v1 = v.clone();
-                let v2 = v;
-                audio_state.decay = 2_f64.log10();
-                audio_state.old_decay = 2_f64.log10();
-                Some((v1, v2))
+        let previous = BuildSnapshot::take(&mut audio_state);
+        let pos = audio_state.params.morph_pos;
+        let plans = (morphs.0.plan_at(pos), morphs.1.plan_at(pos));
+        match audio_state.set_plan(plans, None) {
+            Ok(_) => {
+                audio_state.morph = Some(morphs);
+                audio_state.params.decay = state::DEFAULT_DECAY;
+                // reapply the decay, and the morph position together with the current transpose and shaping, on the next buffer
+                audio_state.old_decay = f64::NAN;
+                audio_state.old_transpose = f64::NAN;
+                audio_state.old_morph_pos = f64::NAN;
             },
             Err(e) => {
                 println!("Error occurred while building resonator array: {:?}", e);
-                None
+                // the plan went with the snapshot
+                audio_state.filter = None;
+            }
+        }
+        std::mem::drop(audio_state);
+        data.history.lock().push_build(previous);
+    });
+
+    let sidechain_button = Label::new(|data: &AppState, _env: &_| {
+        format!("sidechain: {}", data.sidechain_key.name())
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.sidechain_key = data.sidechain_key.next();
+        let mut audio_state = data.audio_state.lock();
+        audio_state.params.sidechain.key = data.sidechain_key;
+        audio_state.sidechain.reset();
+    });
+
+    let sidechain_invert_button = Label::new(|data: &AppState, _env: &_| {
+        if data.sidechain_invert {
+            "loud: rings longer".to_string()
+        } else {
+            "loud: damped".to_string()
+        }
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.sidechain_invert = !data.sidechain_invert;
+        data.audio_state.lock().params.sidechain.invert = data.sidechain_invert;
+    });
+
+    let record_button = Label::new(|data: &AppState, _env: &_| {
+        if data.recording {
+            "Stop recording".to_string()
+        } else {
+            "Record".to_string()
+        }
+    })
+    .with_text_size(24.0)
+    .padding(10.0)
+    .background(Painter::new(|ctx, data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        if data.recording {
+            ctx.fill(bounds, &Color::rgb8(0xe1, 0x1e, 0x1e));
+        } else {
+            ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+        }
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| toggle_recording(data));
+
+    let record_format_button = Label::new(|data: &AppState, _env: &_| {
+        format!("format: {}", data.record_format.name())
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        // applies to the next recording
+        data.record_format = data.record_format.next();
+    });
+
+    let automation_button = Label::new(|data: &AppState, _env: &_| {
+        format!("automation: {}", data.automation_mode.name())
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.automation_mode = data.automation_mode.next();
+        data.audio_state.lock().automation.set_mode(data.automation_mode);
+    });
+
+    let save_automation_button = Label::new("SAVE AUTOMATION")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        match data.audio_state.lock().automation.save(&data.automation_path) {
+            Ok(_) => println!("Saved automation to {}", data.automation_path),
+            Err(e) => println!("Error occurred while saving automation: {:?}", e),
+        }
+    });
+
+    let load_automation_button = Label::new("LOAD AUTOMATION")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        let mut audio_state = data.audio_state.lock();
+        match Automation::load(&data.automation_path, audio_state.sample_rate) {
+            Ok(automation) => {
+                audio_state.automation = automation;
+                audio_state.automation.set_mode(AutomationMode::Play);
+                data.automation_mode = AutomationMode::Play;
+            },
+            Err(e) => println!("Error occurred while loading automation: {:?}", e),
+        }
+    });
+
+    let save_session_button = Label::new("SAVE SESSION")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|ctx, data: &mut AppState, _env| {
+        let session = Session::capture(data, Some(WindowLayout::of(ctx.window())));
+        match session.save(&data.session_path) {
+            Ok(_) => println!("Saved session to {}", data.session_path),
+            Err(e) => println!("Error occurred while saving session: {:?}", e),
+        }
+    });
+
+    let export_format_button = Label::new(|data: &AppState, _env: &_| {
+        format!("export: {}", data.export_format.name())
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.export_format = data.export_format.next();
+    });
+
+    let export_button = Label::new("EXPORT PLAN")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        let audio_state = data.audio_state.lock();
+        let plans = match &audio_state.plan {
+            Some(v) => v,
+            None => {
+                println!("Build a resonator before exporting its plan");
+                return;
             }
         };
-        audio_state.filter = array;
-        audio_state.plan = Some(plan.clone());
+        // the ring time the resonator array is set to, see AudioState::process_filter
+        let decay = 4_f64.powf(audio_state.params.decay) - 1.0;
+        match export::export_plan_to_file(plans, audio_state.sample_rate, decay, data.export_format) {
+            Ok(path) => println!("Exported plan to {}", path.display()),
+            Err(e) => println!("Error occurred while exporting plan: {:?}", e),
+        }
     });
 
-    let max_peaks_label = Label::new("max peaks");
-    let max_peaks_lens = AppState::line_graph.then(GraphData::max_peaks);
+    let import_box = TextBox::new()
+        .with_placeholder("plan file or spec, e.g. C major triad harmonics 1-16")
+        .lens(AppState::import_text)
+        .controller(ImportBoxController)
+        .fix_width(360.0);
+
+    let import_button = Label::new("IMPORT PLAN")
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| import_from_text(data));
+
+    let clear_import_button = Label::new(|data: &AppState, _env: &_| {
+        if data.line_graph.imported.is_some() {
+            "plan: imported".to_string()
+        } else {
+            "plan: analyzed".to_string()
+        }
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.line_graph.clear_imported();
+    });
+
+    let window_button = Label::new(|data: &AppState, _env: &_| {
+        format!("window: {}", data.line_graph.planner.analysis.window.name())
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.line_graph.planner.analysis.window = data.line_graph.planner.analysis.window.next();
+        if let Err(e) = data.line_graph.reanalyze() {
+            println!("Error occurred while computing spectrum: {:?}", e);
+        }
+    });
+
+    let stereo_button = Label::new(|data: &AppState, _env: &_| {
+        if data.line_graph.planner.stereo {
+            "analysis: stereo".to_string()
+        } else {
+            "analysis: mono".to_string()
+        }
+    })
+    .with_text_size(16.0)
+    .padding(6.0)
+    .background(Painter::new(|ctx, _data: &AppState, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(0x7B, 0x61, 0x9E));
+    }))
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.line_graph.planner.stereo = !data.line_graph.planner.stereo;
+        if let Err(e) = data.line_graph.reanalyze() {
+            println!("Error occurred while computing spectrum: {:?}", e);
+        }
+    });
+
+    let max_peaks_label = slider_label("max peaks", SliderParam::MaxPeaks);
+    let max_peaks_lens = AppState::line_graph.then(GraphData::planner).then(druid::lens!(PlannerSettings, max_peaks));
     let max_peaks_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
         .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
-        .lens(max_peaks_lens);
+        .lens(max_peaks_lens)
+        .controller(SliderFocus::new(SliderParam::MaxPeaks));
 
-    let min_prom_label = Label::new("min prominence");
-    let min_prom_lens = AppState::line_graph.then(GraphData::min_prominence);
+    let min_prom_label = slider_label("min prominence", SliderParam::MinProminence);
+    let min_prom_lens = AppState::line_graph.then(GraphData::planner).then(druid::lens!(PlannerSettings, min_prominence));
     let min_prom_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
         .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
-        .lens(min_prom_lens);
+        .lens(min_prom_lens)
+        .controller(SliderFocus::new(SliderParam::MinProminence));
 
-    let min_thresh_label = Label::new("min threshold");
-    let min_line_lens = AppState::line_graph.then(GraphData::min_line);
+    let min_thresh_label = slider_label("min threshold", SliderParam::MinThreshold);
+    let min_line_lens = AppState::line_graph.then(GraphData::planner).then(druid::lens!(PlannerSettings, min_line));
     let thresh_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
         .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
-        .lens(min_line_lens);
+        .lens(min_line_lens)
+        .controller(SliderFocus::new(SliderParam::MinThreshold));
 
-    let min_freq_label = Label::new("min freq");
-    let min_freq_lens = AppState::line_graph.then(GraphData::min_range);
+    let min_freq_label = slider_label("min freq", SliderParam::MinFreq);
+    let min_freq_lens = AppState::line_graph.then(GraphData::planner).then(druid::lens!(PlannerSettings, min_range));
     let min_freq_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
         .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
-        .lens(min_freq_lens);
+        .lens(min_freq_lens)
+        .controller(SliderFocus::new(SliderParam::MinFreq));
 
-    let max_freq_label = Label::new("max freq");
-    let max_freq_lens = AppState::line_graph.then(GraphData::max_range);
+    let max_freq_label = slider_label("max freq", SliderParam::MaxFreq);
+    let max_freq_lens = AppState::line_graph.then(GraphData::planner).then(druid::lens!(PlannerSettings, max_range));
     let max_freq_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
         .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
-        .lens(max_freq_lens);
+        .lens(max_freq_lens)
+        .controller(SliderFocus::new(SliderParam::MaxFreq));
 
-    let decay_label = Label::new("Decay");
+    let decay_label = slider_label("Decay", SliderParam::Decay);
     let decay_slider = Slider::new()
         .with_range(0.0, 1.0)
         .with_step(0.0001)
@@ -318,9 +841,10 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
         .lens(AudioDecayLens)
+        .controller(SliderFocus::new(SliderParam::Decay))
         .fix_height(200.0);
 
-    let volume_label = Label::new("Volume");
+    let volume_label = slider_label("Volume", SliderParam::Volume);
     let volume_slider = Slider::new()
         .with_range(-40.0, 6.0)
         .with_step(0.001)
@@ -328,9 +852,10 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
         .lens(AudioVolumeLens)
+        .controller(SliderFocus::new(SliderParam::Volume))
         .fix_height(200.0);
 
-    let transpose_label = Label::new("Transpose");
+    let transpose_label = slider_label("Transpose", SliderParam::Transpose);
     let transpose_slider = Slider::new()
         .with_range(-1.0, 1.0)
         .with_step(0.001)
@@ -338,6 +863,73 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
         .knob_style(druid::widget::KnobStyle::Circle)
         .axis(Axis::Vertical)
         .lens(AudioTransposeLens)
+        .controller(SliderFocus::new(SliderParam::Transpose))
+        .fix_height(200.0);
+
+    let morph_label = slider_label("Morph", SliderParam::Morph);
+    let morph_slider = Slider::new()
+        .with_range(0.0, 1.0)
+        .with_step(0.0001)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(AudioMorphLens)
+        .controller(SliderFocus::new(SliderParam::Morph))
+        .fix_height(200.0);
+
+    let attack_label = slider_label("Attack", SliderParam::SidechainAttack);
+    let attack_slider = Slider::new()
+        .with_range(0.001, 0.5)
+        .with_step(0.001)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(SidechainLens(|s| &mut s.attack))
+        .controller(SliderFocus::new(SliderParam::SidechainAttack))
+        .fix_height(200.0);
+
+    let release_label = slider_label("Release", SliderParam::SidechainRelease);
+    let release_slider = Slider::new()
+        .with_range(0.01, 2.0)
+        .with_step(0.001)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(SidechainLens(|s| &mut s.release))
+        .controller(SliderFocus::new(SliderParam::SidechainRelease))
+        .fix_height(200.0);
+
+    let depth_label = slider_label("Depth", SliderParam::SidechainDepth);
+    let depth_slider = Slider::new()
+        .with_range(0.0, 1.0)
+        .with_step(0.0001)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(SidechainLens(|s| &mut s.depth))
+        .controller(SliderFocus::new(SliderParam::SidechainDepth))
+        .fix_height(200.0);
+
+    let decay_tilt_label = slider_label("Decay tilt", SliderParam::DecayTilt);
+    let decay_tilt_slider = Slider::new()
+        .with_range(0.25, 1.5)
+        .with_step(0.001)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(ShapingLens(|s| &mut s.decay_tilt))
+        .controller(SliderFocus::new(SliderParam::DecayTilt))
+        .fix_height(200.0);
+
+    let gain_tilt_label = slider_label("Gain tilt", SliderParam::GainTilt);
+    let gain_tilt_slider = Slider::new()
+        .with_range(-12.0, 6.0)
+        .with_step(0.01)
+        .track_color(druid::KeyOrValue::Concrete(Color::rgb8(0x7B, 0x61, 0x9E)))
+        .knob_style(druid::widget::KnobStyle::Circle)
+        .axis(Axis::Vertical)
+        .lens(ShapingLens(|s| &mut s.gain_tilt))
+        .controller(SliderFocus::new(SliderParam::GainTilt))
         .fix_height(200.0);
 
     Flex::column()
@@ -345,6 +937,12 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
             Flex::row()
                 .with_child(play_pause_button)
                 .with_spacer(8.0)
+                .with_child(stop_button)
+                .with_spacer(8.0)
+                .with_child(loop_button)
+                .with_spacer(8.0)
+                .with_child(time_label)
+                .with_spacer(8.0)
                 .with_child(label),
         )
         .with_child(progress_bar)
@@ -353,11 +951,42 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
             Flex::row()
                 .with_child(r_play_pause_button)
                 .with_spacer(8.0)
+                .with_child(r_stop_button)
+                .with_spacer(8.0)
+                .with_child(r_loop_button)
+                .with_spacer(8.0)
+                .with_child(r_time_label)
+                .with_spacer(8.0)
                 .with_child(r_label),
         )
         .with_child(r_progress_bar)
         .with_spacer(8.0)
+        .with_child(spectrogram)
         .with_child(graph)
+        .with_spacer(8.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(analyzer, 1.0)
+                .with_spacer(8.0)
+                .with_child(meter_row)
+        )
+        .with_child(
+            Flex::row()
+                .with_child(window_button)
+                .with_spacer(8.0)
+                .with_child(stereo_button)
+                .with_spacer(24.0)
+                .with_child(record_button)
+                .with_spacer(8.0)
+                .with_child(record_format_button)
+                .with_spacer(24.0)
+                .with_child(save_session_button)
+                .with_spacer(24.0)
+                .with_child(export_format_button)
+                .with_spacer(8.0)
+                .with_child(export_button)
+        )
+        .with_spacer(8.0)
         .with_child(
             Flex::row()
                 .with_child(
@@ -393,6 +1022,15 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
                 .with_child(build_button)
         )
         .with_spacer(8.0)
+        .with_child(
+            Flex::row()
+                .with_child(import_box)
+                .with_spacer(8.0)
+                .with_child(import_button)
+                .with_spacer(8.0)
+                .with_child(clear_import_button)
+        )
+        .with_spacer(8.0)
         .with_child(
             Flex::row()
                 .with_child(
@@ -412,24 +1050,128 @@ fn build_ui(audio_text: String, r_audio_text: String) -> impl druid::Widget<AppS
                         .with_child(transpose_label)
                         .with_child(transpose_slider)
                 )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(morph_label)
+                        .with_child(morph_slider)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(automation_button)
+                        .with_spacer(8.0)
+                        .with_child(save_automation_button)
+                        .with_spacer(8.0)
+                        .with_child(load_automation_button)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(store_a_button)
+                        .with_spacer(8.0)
+                        .with_child(store_b_button)
+                        .with_spacer(8.0)
+                        .with_child(build_morph_button)
+                )
+                .with_spacer(24.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(sidechain_button)
+                        .with_spacer(8.0)
+                        .with_child(sidechain_invert_button)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(attack_label)
+                        .with_child(attack_slider)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(release_label)
+                        .with_child(release_slider)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(depth_label)
+                        .with_child(depth_slider)
+                )
+                .with_spacer(24.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(decay_tilt_label)
+                        .with_child(decay_tilt_slider)
+                )
+                .with_spacer(8.0)
+                .with_child(
+                    Flex::column()
+                        .with_child(gain_tilt_label)
+                        .with_child(gain_tilt_slider)
+                )
         )
+        .controller(TransportController::new())
+        .controller(HistoryController::new())
+        .controller(KeyController::new(key_bindings))
+}
 
+// the name of a slider, marked when the arrow keys move it
+fn slider_label(name: &'static str, param: SliderParam) -> Label<AppState> {
+    Label::new(move |data: &AppState, _env: &_| {
+        if data.focused_slider == Some(param) {
+            format!("> {}", name)
+        } else {
+            name.to_string()
+        }
+    })
+}
+
+// builds the resonator array for the source track from the current plan
+fn build_resonator(data: &mut AppState) {
+    let plans = data.line_graph.current_plans();
+    let mut audio_state = data.audio_state.lock();
+    let previous = BuildSnapshot::take(&mut audio_state);
+    match audio_state.set_plan(plans, Some((data.line_graph.planner.region_start, data.line_graph.planner.region_end))) {
+        Ok(_) => {
+            // an imported ring time replaces the default decay, see AudioState::process_filter
+            audio_state.params.decay = data.line_graph.imported_decay.map(state::decay_for_ring_time).unwrap_or(state::DEFAULT_DECAY);
+            // the new arrays have neither the decay nor the transpose applied yet
+            audio_state.old_decay = f64::NAN;
+            audio_state.old_transpose = f64::NAN;
+        },
+        Err(e) => println!("Error occurred while building resonator array: {:?}", e),
+    }
+    std::mem::drop(audio_state);
+    data.history.lock().push_build(previous);
 }
 
-#[inline]
-fn load_audio<P: AsRef<Path>>(path: P) -> Result<([Vec<f32>; 2], f64), Box<dyn Error>> {
-    let file = File::open(path)?;
-    let source = Decoder::new(BufReader::new(file))?;
-    let sample_rate = source.sample_rate() as f64;
-    let channels = source.channels();
-    if channels != 2 {
-        return Err("This app only supports audio files with 2 channels :C".into());
+// uses the plan from the file or spec in the import box instead of the planner
+fn import_from_text(data: &mut AppState) {
+    let sample_rate = data.audio_state.lock().sample_rate;
+    match import_plan(&data.import_text, sample_rate) {
+        Ok(imported) => data.line_graph.set_imported(imported),
+        Err(e) => println!("Error occurred while importing plan: {:?}", e),
     }
-    let mut samples = [Vec::new(), Vec::new()];
-    let mut cur = 0;
-    for v in source.convert_samples::<f32>() {
-        samples[cur].push(v);
-        cur = (cur + 1) % 2;
+}
+
+// starts a new recording in ./recordings, or finishes the current one
+fn toggle_recording(data: &mut AppState) {
+    let mut recorder = data.recorder.lock();
+    if recorder.is_recording() {
+        if let Err(e) = recorder.stop() {
+            println!("Error occurred while finishing recording: {:?}", e);
+        }
+        data.recording = false;
+        return;
     }
-    Ok((samples, sample_rate))
-}
\ No newline at end of file
+    let audio_state = data.audio_state.lock();
+    let result = recorder::default_recording_path()
+        .and_then(|path| recorder.start(path, data.record_format, audio_state.plan.as_ref(), audio_state.sample_rate));
+    std::mem::drop(audio_state);
+    match result {
+        Ok(_) => data.recording = true,
+        Err(e) => println!("Error occurred while starting recording: {:?}", e),
+    }
+}
@@ -0,0 +1,92 @@
+use druid::widget::prelude::*;
+use druid::{Color, Rect, TimerToken};
+use druid::kurbo::{BezPath, Point};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::level::LevelMeter;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(16);
+// lowest level shown on the meter
+const MIN_DB: f64 = -60.0;
+// how fast the displayed peak falls, in db per refresh
+const PEAK_FALLOFF_DB: f64 = 1.0;
+
+#[inline]
+fn level_to_fraction(level: f32) -> f64 {
+    let db = 20.0 * (level as f64).max(1e-9).log10();
+    ((db - MIN_DB) / -MIN_DB).max(0.0).min(1.0)
+}
+
+// a vertical peak/rms meter with a clip indicator on top. click to reset the clip indicator
+pub struct MeterView {
+    meter: Arc<LevelMeter>,
+    timer: TimerToken,
+    // displayed peak as a fraction of the meter height
+    peak: f64,
+}
+
+impl MeterView {
+    pub fn new(meter: Arc<LevelMeter>) -> Self {
+        Self {
+            meter,
+            timer: TimerToken::INVALID,
+            peak: 0.0,
+        }
+    }
+}
+
+impl<T: Data> Widget<T> for MeterView {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, _env: &Env) {
+        match event {
+            Event::WindowConnected => {
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+            },
+            Event::Timer(token) if *token == self.timer => {
+                let peak = level_to_fraction(self.meter.take_peak());
+                let falloff = PEAK_FALLOFF_DB / -MIN_DB;
+                self.peak = peak.max(self.peak - falloff);
+                ctx.request_paint();
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+            },
+            Event::MouseDown(_) => {
+                self.meter.reset_clip();
+                ctx.request_paint();
+                ctx.set_handled();
+            },
+            _ => {},
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &T, _env: &Env) {}
+
+    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &T, _data: &T, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &T, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &T, _env: &Env) {
+        let size = ctx.size();
+        let clip_height = 8.0;
+        let height = size.height - clip_height - 2.0;
+        let bg = Rect::new(0.0, 0.0, size.width, size.height);
+        ctx.fill(bg, &Color::rgb8(0, 0, 0));
+
+        let clip_color = if self.meter.clipped() {
+            Color::rgb8(0xe1, 0x1e, 0x1e)
+        } else {
+            Color::grey(0.2)
+        };
+        ctx.fill(Rect::new(0.0, 0.0, size.width, clip_height), &clip_color);
+
+        let rms = level_to_fraction(self.meter.rms());
+        let rms_rect = Rect::new(0.0, size.height - rms * height, size.width, size.height);
+        ctx.fill(rms_rect, &Color::rgb8(0x7B, 0x61, 0x9E));
+
+        let y = size.height - self.peak * height;
+        let mut path = BezPath::new();
+        path.move_to(Point::new(0.0, y));
+        path.line_to(Point::new(size.width, y));
+        ctx.stroke(path, &Color::rgb8(0x1e, 0xcb, 0xe1), 2.0);
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::sync::Arc;
+use parking_lot::Mutex;
+use midir::{MidiInput, MidiInputConnection};
+use crate::state::AudioState;
+
+const CLIENT_NAME: &str = "Capstone Project Demo";
+const VIRTUAL_PORT_NAME: &str = "resonator in";
+
+pub struct MidiConfig {
+    // connect to the first input port whose name contains this, or create a virtual port when None
+    pub port: Option<String>,
+    // the note that plays the resonator untransposed
+    pub root_note: u8,
+    // pitch bend range in semitones
+    pub bend_range: f64,
+    pub decay_cc: u8,
+    pub volume_cc: u8,
+    pub morph_cc: u8,
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            root_note: 60,
+            bend_range: 2.0,
+            // mod wheel, channel volume and expression
+            decay_cc: 1,
+            volume_cc: 7,
+            morph_cc: 11,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+    // value between -1.0 and 1.0
+    PitchBend(f64),
+    ControlChange { controller: u8, value: u8 },
+}
+
+impl MidiMessage {
+    // parses a channel voice message, ignoring the channel
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        match status & 0xF0 {
+            0x80 => Some(MidiMessage::NoteOff { note: *bytes.get(1)? }),
+            // note on with velocity 0 is a note off
+            0x90 if *bytes.get(2)? == 0 => Some(MidiMessage::NoteOff { note: *bytes.get(1)? }),
+            0x90 => Some(MidiMessage::NoteOn { note: *bytes.get(1)?, velocity: *bytes.get(2)? }),
+            0xB0 => Some(MidiMessage::ControlChange { controller: *bytes.get(1)?, value: *bytes.get(2)? }),
+            0xE0 => {
+                let value = (*bytes.get(1)? as u16) | ((*bytes.get(2)? as u16) << 7);
+                Some(MidiMessage::PitchBend((value as f64 - 8192.0) / 8192.0))
+            },
+            _ => None,
+        }
+    }
+}
+
+// applies incoming midi messages to an AudioState
+pub struct MidiHandler {
+    audio: Arc<Mutex<AudioState>>,
+    config: MidiConfig,
+    // held notes in the order they were pressed, the last one sets the pitch
+    held: Vec<u8>,
+    bend: f64,
+}
+
+impl MidiHandler {
+    pub fn new(audio: Arc<Mutex<AudioState>>, config: MidiConfig) -> Self {
+        Self {
+            audio,
+            config,
+            held: Vec::new(),
+            bend: 0.0,
+        }
+    }
+
+    pub fn handle(&mut self, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { note, velocity } => {
+                self.held.retain(|n| *n != note);
+                self.held.push(note);
+                let mut audio = self.audio.lock();
+                audio.input_gain = velocity as f64 / 127.0;
+                std::mem::drop(audio);
+                self.update_transpose();
+            },
+            MidiMessage::NoteOff { note } => {
+                self.held.retain(|n| *n != note);
+                if self.held.is_empty() {
+                    // let the resonator ring out
+                    self.audio.lock().input_gain = 0.0;
+                } else {
+                    self.update_transpose();
+                }
+            },
+            MidiMessage::PitchBend(bend) => {
+                self.bend = bend;
+                self.update_transpose();
+            },
+            MidiMessage::ControlChange { controller, value } => {
+                let v = value as f64 / 127.0;
+                let mut audio = self.audio.lock();
+                if controller == self.config.decay_cc {
+                    audio.params.decay = v;
+                } else if controller == self.config.volume_cc {
+                    // same range as the volume slider
+                    audio.params.volume = -40.0 + v * 46.0;
+                } else if controller == self.config.morph_cc {
+                    audio.params.morph_pos = v;
+                }
+            },
+        }
+    }
+
+    fn update_transpose(&mut self) {
+        let note = self.held.last().copied().unwrap_or(self.config.root_note);
+        let semitones = note as f64 - self.config.root_note as f64 + self.bend * self.config.bend_range;
+        self.audio.lock().params.transpose = semitones / 12.0;
+    }
+}
+
+// the connection has to be kept alive for as long as midi should be received
+pub fn connect_midi(audio: Arc<Mutex<AudioState>>, config: MidiConfig) -> Result<MidiInputConnection<MidiHandler>, Box<dyn Error>> {
+    let midi_in = MidiInput::new(CLIENT_NAME)?;
+    let callback = |_stamp: u64, bytes: &[u8], handler: &mut MidiHandler| {
+        if let Some(message) = MidiMessage::parse(bytes) {
+            handler.handle(message);
+        }
+    };
+
+    match config.port.clone() {
+        Some(name) => {
+            let port = midi_in.ports()
+                .into_iter()
+                .find(|p| midi_in.port_name(p).map(|n| n.contains(&name)).unwrap_or(false))
+                .ok_or_else(|| format!("No midi input port matching '{}'", name))?;
+            let connection = midi_in.connect(&port, VIRTUAL_PORT_NAME, callback, MidiHandler::new(audio, config))
+                .map_err(|e| format!("Error while connecting to midi port: {}", e))?;
+            Ok(connection)
+        },
+        None => create_virtual_port(midi_in, callback, MidiHandler::new(audio, config)),
+    }
+}
+
+#[cfg(unix)]
+fn create_virtual_port<F>(midi_in: MidiInput, callback: F, handler: MidiHandler) -> Result<MidiInputConnection<MidiHandler>, Box<dyn Error>>
+where F: FnMut(u64, &[u8], &mut MidiHandler) + Send + 'static {
+    use midir::os::unix::VirtualInput;
+    let connection = midi_in.create_virtual(VIRTUAL_PORT_NAME, callback, handler)
+        .map_err(|e| format!("Error while creating virtual midi port: {}", e))?;
+    Ok(connection)
+}
+
+#[cfg(not(unix))]
+fn create_virtual_port<F>(_midi_in: MidiInput, _callback: F, _handler: MidiHandler) -> Result<MidiInputConnection<MidiHandler>, Box<dyn Error>>
+where F: FnMut(u64, &[u8], &mut MidiHandler) + Send + 'static {
+    Err("Virtual midi ports are not supported on this platform, pass --midi-port".into())
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+pub const LFO_COUNT: usize = 2;
+pub const ENVELOPE_COUNT: usize = 2;
+// shortest envelope stage in seconds
+const MIN_STAGE: f64 = 0.001;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    // a new random value every cycle
+    Random,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LfoSettings {
+    pub shape: LfoShape,
+    // cycles per second
+    pub rate: f64,
+    // length of a cycle in beats of ModulationSettings::tempo, replaces rate when set
+    pub sync: Option<f64>,
+}
+
+impl Default for LfoSettings {
+    fn default() -> Self {
+        Self {
+            shape: LfoShape::Sine,
+            rate: 1.0,
+            sync: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EnvelopeSettings {
+    // seconds
+    pub attack: f64,
+    pub decay: f64,
+    pub release: f64,
+    // level held after the decay until playback stops, 0.0 to 1.0
+    pub sustain: f64,
+    // start again every time the loop wraps around, not only when playback starts
+    pub on_loop: bool,
+}
+
+impl Default for EnvelopeSettings {
+    fn default() -> Self {
+        Self {
+            attack: 0.01,
+            decay: 0.5,
+            release: 0.5,
+            sustain: 0.5,
+            on_loop: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ModSource {
+    // index into ModulationSettings::lfos, -1.0 to 1.0
+    Lfo(usize),
+    // index into ModulationSettings::envelopes, 0.0 to 1.0
+    Envelope(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ModTarget {
+    Decay,
+    Transpose,
+    Volume,
+}
+
+// one entry of the routing matrix
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ModRoute {
+    pub source: ModSource,
+    pub target: ModTarget,
+    // in the target's units at full scale: 0.0 to 1.0 of decay, octaves of transpose, db of volume
+    pub depth: f64,
+}
+
+// the lfos, envelopes and the routing between them and the parameters of a track, e.g.
+// {"tempo": 120.0, "routes": [{"source": {"Lfo": 0}, "target": "Transpose", "depth": 0.05}]}
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModulationSettings {
+    // beats per minute for synced lfos
+    pub tempo: f64,
+    pub lfos: [LfoSettings; LFO_COUNT],
+    pub envelopes: [EnvelopeSettings; ENVELOPE_COUNT],
+    // nothing is modulated while this is empty
+    pub routes: Vec<ModRoute>,
+}
+
+impl Default for ModulationSettings {
+    fn default() -> Self {
+        Self {
+            tempo: 120.0,
+            lfos: [LfoSettings::default(); LFO_COUNT],
+            envelopes: [EnvelopeSettings::default(); ENVELOPE_COUNT],
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl ModulationSettings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let settings: ModulationSettings = serde_json::from_str(&fs::read_to_string(path)?)?;
+        for route in settings.routes.iter() {
+            let in_range = match route.source {
+                ModSource::Lfo(i) => i < LFO_COUNT,
+                ModSource::Envelope(i) => i < ENVELOPE_COUNT,
+            };
+            if !in_range {
+                return Err(format!("Unknown modulation source {:?}", route.source).into());
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        !self.routes.is_empty()
+    }
+}
+
+// what the modulation adds to each parameter for a block
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ModOffsets {
+    pub decay: f64,
+    pub transpose: f64,
+    pub volume: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Clone, Copy)]
+struct Envelope {
+    stage: Stage,
+    level: f64,
+}
+
+impl Envelope {
+    // moves the envelope forward by dt seconds
+    #[inline]
+    fn advance(&mut self, settings: &EnvelopeSettings, dt: f64) {
+        let sustain = settings.sustain.max(0.0).min(1.0);
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Attack => {
+                self.level += dt / settings.attack.max(MIN_STAGE);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            },
+            Stage::Decay => {
+                self.level -= dt * (1.0 - sustain) / settings.decay.max(MIN_STAGE);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.stage = Stage::Sustain;
+                }
+            },
+            Stage::Sustain => self.level = sustain,
+            Stage::Release => {
+                self.level -= dt / settings.release.max(MIN_STAGE);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            },
+        }
+    }
+}
+
+// runs the lfos and envelopes of a track. advance is called once per block from the audio thread
+// before the block goes through the resonators, the offsets hold for the whole block. the settings
+// are kept with the track's other parameters and passed in
+pub struct Modulation {
+    // 0.0 to 1.0 through the cycle
+    lfo_phase: [f64; LFO_COUNT],
+    // the held value of random lfos
+    lfo_random: [f64; LFO_COUNT],
+    rng: u32,
+    envelopes: [Envelope; ENVELOPE_COUNT],
+    // envelopes start on the first block after playback starts
+    triggered: bool,
+    offsets: ModOffsets,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            lfo_phase: [0.0; LFO_COUNT],
+            lfo_random: [0.0; LFO_COUNT],
+            rng: 0x9E37_79B9,
+            envelopes: [Envelope { stage: Stage::Idle, level: 0.0 }; ENVELOPE_COUNT],
+            triggered: false,
+            offsets: ModOffsets::default(),
+        }
+    }
+}
+
+impl Modulation {
+    // the offsets of the last block
+    #[inline]
+    pub fn offsets(&self) -> ModOffsets {
+        self.offsets
+    }
+
+    // playback started, the envelopes and synced lfos start over on the next block
+    pub fn trigger(&mut self) {
+        self.triggered = false;
+    }
+
+    // playback stopped, the envelopes go into their release
+    pub fn release(&mut self) {
+        self.triggered = true;
+        for envelope in self.envelopes.iter_mut() {
+            if envelope.stage != Stage::Idle {
+                envelope.stage = Stage::Release;
+            }
+        }
+    }
+
+    // the loop wrapped around
+    pub fn looped(&mut self, settings: &ModulationSettings) {
+        for (envelope, settings) in self.envelopes.iter_mut().zip(settings.envelopes.iter()) {
+            if settings.on_loop {
+                envelope.stage = Stage::Attack;
+            }
+        }
+    }
+
+    #[inline]
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f64 / u32::MAX as f64 * 2.0 - 1.0
+    }
+
+    #[inline]
+    fn lfo_value(&self, settings: &ModulationSettings, i: usize) -> f64 {
+        let phase = self.lfo_phase[i];
+        match settings.lfos[i].shape {
+            LfoShape::Sine => (2.0 * PI * phase).sin(),
+            // starts at 0.0 and rises like the sine
+            LfoShape::Triangle => 1.0 - 4.0 * ((phase + 0.25).fract() - 0.5).abs(),
+            LfoShape::Random => self.lfo_random[i],
+        }
+    }
+
+    #[inline]
+    fn source_value(&self, settings: &ModulationSettings, source: ModSource) -> f64 {
+        match source {
+            ModSource::Lfo(i) if i < LFO_COUNT => self.lfo_value(settings, i),
+            ModSource::Envelope(i) if i < ENVELOPE_COUNT => self.envelopes[i].level,
+            _ => 0.0,
+        }
+    }
+
+    // computes the offsets for the next block of frames and moves the sources past it
+    #[inline]
+    pub fn advance(&mut self, settings: &ModulationSettings, frames: usize, sample_rate: f64) -> ModOffsets {
+        if !self.triggered {
+            self.triggered = true;
+            for i in 0..LFO_COUNT {
+                if settings.lfos[i].sync.is_some() {
+                    self.lfo_phase[i] = 0.0;
+                }
+            }
+            for envelope in self.envelopes.iter_mut() {
+                // from the current level so a retrigger doesn't click
+                envelope.stage = Stage::Attack;
+            }
+        }
+
+        let mut offsets = ModOffsets::default();
+        for route in settings.routes.iter() {
+            let v = route.depth * self.source_value(settings, route.source);
+            match route.target {
+                ModTarget::Decay => offsets.decay += v,
+                ModTarget::Transpose => offsets.transpose += v,
+                ModTarget::Volume => offsets.volume += v,
+            }
+        }
+        self.offsets = offsets;
+
+        let dt = frames as f64 / sample_rate;
+        for i in 0..LFO_COUNT {
+            let lfo = settings.lfos[i];
+            let rate = match lfo.sync {
+                Some(beats) => settings.tempo / 60.0 / beats.max(1e-3),
+                None => lfo.rate,
+            };
+            self.lfo_phase[i] += dt * rate.max(0.0);
+            if self.lfo_phase[i] >= 1.0 {
+                self.lfo_phase[i] = self.lfo_phase[i].fract();
+                self.lfo_random[i] = self.next_random();
+            }
+        }
+        for i in 0..ENVELOPE_COUNT {
+            self.envelopes[i].advance(&settings.envelopes[i], dt);
+        }
+        offsets
+    }
+}
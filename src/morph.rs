@@ -0,0 +1,76 @@
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use serde::{Deserialize, Serialize};
+
+// resonators whose frequencies are within this ratio of each other are treated as the same partial
+const MATCH_RATIO: f64 = 1.03;
+
+// a resonator array layout that can move between two plans without being rebuilt.
+// every resonator has a start (arg, amplitude) at position 0.0 and an end at position 1.0.
+// resonators that only exist in one of the plans keep their frequency and fade in or out
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MorphPlan {
+    pub pairs: Vec<((f64, f64), (f64, f64))>,
+}
+
+impl MorphPlan {
+    pub fn new(a: &ScaledResonatorPlan, b: &ScaledResonatorPlan) -> Self {
+        // greedily match the closest resonators first
+        let mut candidates = Vec::new();
+        for (i, ra) in a.resonators.iter().enumerate() {
+            for (j, rb) in b.resonators.iter().enumerate() {
+                let distance = (ra.0 / rb.0).ln().abs();
+                if distance < MATCH_RATIO.ln() {
+                    candidates.push((distance, i, j));
+                }
+            }
+        }
+        candidates.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut a_used = vec![false; a.resonators.len()];
+        let mut b_used = vec![false; b.resonators.len()];
+        let mut pairs = Vec::with_capacity(a.resonators.len().max(b.resonators.len()));
+        for (_, i, j) in candidates {
+            if a_used[i] || b_used[j] {
+                continue;
+            }
+            a_used[i] = true;
+            b_used[j] = true;
+            pairs.push((a.resonators[i], b.resonators[j]));
+        }
+        for (i, ra) in a.resonators.iter().enumerate() {
+            if !a_used[i] {
+                pairs.push((*ra, (ra.0, 0.0)));
+            }
+        }
+        for (j, rb) in b.resonators.iter().enumerate() {
+            if !b_used[j] {
+                pairs.push(((rb.0, 0.0), *rb));
+            }
+        }
+        Self {
+            pairs,
+        }
+    }
+
+    // the plan to build the resonator array from
+    pub fn plan_at(&self, pos: f64) -> ScaledResonatorPlan {
+        let mut plan = ScaledResonatorPlan::empty();
+        plan.resonators = (0..self.pairs.len())
+            .map(|i| (self.arg_at(i, pos), self.amp_at(i, pos)))
+            .collect();
+        plan
+    }
+
+    // frequencies are interpolated on a log scale so the morph moves evenly in pitch
+    #[inline]
+    pub fn arg_at(&self, index: usize, pos: f64) -> f64 {
+        let ((a, _), (b, _)) = self.pairs[index];
+        a * (b / a).powf(pos)
+    }
+
+    #[inline]
+    pub fn amp_at(&self, index: usize, pos: f64) -> f64 {
+        let ((_, a), (_, b)) = self.pairs[index];
+        a + (b - a) * pos
+    }
+}
@@ -0,0 +1,225 @@
+use std::error::Error;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use druid::ExtEventSink;
+use crate::state::AudioState;
+use crate::AppState;
+
+// how often playback progress is sent back to clients
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_PACKET_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+impl OscArg {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            OscArg::Int(v) => Some(*v as f64),
+            OscArg::Float(v) => Some(*v as f64),
+            OscArg::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            OscArg::Str(_) => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_f64().map(|v| v != 0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub addr: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(addr: &str, args: Vec<OscArg>) -> Self {
+        Self {
+            addr: addr.to_string(),
+            args,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_padded_str(&mut out, &self.addr);
+        let mut tags = ",".to_string();
+        for arg in &self.args {
+            tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::Str(_) => 's',
+                OscArg::Bool(true) => 'T',
+                OscArg::Bool(false) => 'F',
+            });
+        }
+        write_padded_str(&mut out, &tags);
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(v) => out.extend_from_slice(&v.to_be_bytes()),
+                OscArg::Float(v) => out.extend_from_slice(&v.to_be_bytes()),
+                OscArg::Str(v) => write_padded_str(&mut out, v),
+                OscArg::Bool(_) => {},
+            }
+        }
+        out
+    }
+
+    // decodes a packet, flattening bundles into their messages
+    pub fn decode(packet: &[u8]) -> Option<Vec<OscMessage>> {
+        let mut cursor = 0;
+        let first = read_padded_str(packet, &mut cursor)?;
+        if first == "#bundle" {
+            // skip the time tag
+            cursor += 8;
+            let mut messages = Vec::new();
+            while cursor + 4 <= packet.len() {
+                let size = read_i32(packet, &mut cursor)?;
+                if size < 0 {
+                    return None;
+                }
+                let end = cursor.checked_add(size as usize)?;
+                messages.extend(Self::decode(packet.get(cursor..end)?)?);
+                cursor = end;
+            }
+            return Some(messages);
+        }
+
+        let mut args = Vec::new();
+        // a message without a type tag string has no arguments
+        if cursor < packet.len() {
+            let tags = read_padded_str(packet, &mut cursor)?;
+            for tag in tags.chars().skip(1) {
+                args.push(match tag {
+                    'i' => OscArg::Int(read_i32(packet, &mut cursor)?),
+                    'f' => OscArg::Float(f32::from_bits(read_i32(packet, &mut cursor)? as u32)),
+                    's' => OscArg::Str(read_padded_str(packet, &mut cursor)?),
+                    'T' => OscArg::Bool(true),
+                    'F' => OscArg::Bool(false),
+                    _ => return None,
+                });
+            }
+        }
+        Some(vec![Self { addr: first, args }])
+    }
+}
+
+#[inline]
+fn write_padded_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    // null terminated and padded to a multiple of 4 bytes
+    let padding = 4 - s.len() % 4;
+    out.extend(std::iter::repeat(0).take(padding));
+}
+
+#[inline]
+fn read_padded_str(packet: &[u8], cursor: &mut usize) -> Option<String> {
+    let rest = packet.get(*cursor..)?;
+    let len = rest.iter().position(|b| *b == 0)?;
+    let s = String::from_utf8(rest[..len].to_vec()).ok()?;
+    *cursor += len + 4 - len % 4;
+    Some(s)
+}
+
+#[inline]
+fn read_i32(packet: &[u8], cursor: &mut usize) -> Option<i32> {
+    let bytes = packet.get(*cursor..*cursor + 4)?;
+    *cursor += 4;
+    Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// listens on localhost for control messages:
+//   /source/play [bool]    /resonant/play [bool]     toggle or set playing
+//   /source/seek f         /resonant/seek f          position between 0.0 and 1.0
+//   /decay f  /volume f  /transpose f  /morph f
+//   /planner/max_peaks f  /planner/min_prominence f  /planner/min_threshold f
+//   /planner/min_freq f  /planner/max_freq f
+//   /build
+// every client that sent a message receives /source/progress f and /resonant/progress f
+pub fn start_osc_server(port: u16, audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<AudioState>>, sink: ExtEventSink) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    let socket = UdpSocket::bind(("127.0.0.1", port))?;
+    socket.set_read_timeout(Some(PROGRESS_INTERVAL))?;
+
+    let handle = std::thread::spawn(move || {
+        let mut clients: Vec<SocketAddr> = Vec::new();
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let mut last_progress = Instant::now();
+        loop {
+            if let Ok((size, addr)) = socket.recv_from(&mut buf) {
+                if !clients.contains(&addr) {
+                    clients.push(addr);
+                }
+                match OscMessage::decode(&buf[..size]) {
+                    Some(messages) => {
+                        for message in messages {
+                            handle_message(&message, &audio, &r_audio, &sink);
+                        }
+                    },
+                    None => println!("Received malformed osc packet from {}", addr),
+                }
+            }
+
+            if last_progress.elapsed() >= PROGRESS_INTERVAL && !clients.is_empty() {
+                last_progress = Instant::now();
+                let progress = [
+                    OscMessage::new("/source/progress", vec![OscArg::Float(audio.lock().get_progress() as f32)]).encode(),
+                    OscMessage::new("/resonant/progress", vec![OscArg::Float(r_audio.lock().get_progress() as f32)]).encode(),
+                ];
+                // forget clients that went away
+                clients.retain(|addr| progress.iter().all(|p| socket.send_to(p, addr).is_ok()));
+            }
+        }
+    });
+    Ok(handle)
+}
+
+fn handle_message(message: &OscMessage, audio: &Arc<Mutex<AudioState>>, r_audio: &Arc<Mutex<AudioState>>, sink: &ExtEventSink) {
+    let arg = message.args.first();
+    let value = arg.and_then(|a| a.as_f64());
+    match (message.addr.as_str(), value) {
+        ("/source/play", _) => {
+            let playing = arg.and_then(|a| a.as_bool());
+            let _ = sink.add_idle_callback(move |data: &mut AppState| {
+                data.playing = playing.unwrap_or(!data.playing);
+                data.audio_state.lock().set_playing(data.playing);
+            });
+        },
+        ("/resonant/play", _) => {
+            let playing = arg.and_then(|a| a.as_bool());
+            let _ = sink.add_idle_callback(move |data: &mut AppState| {
+                data.r_playing = playing.unwrap_or(!data.r_playing);
+                data.r_audio_state.lock().set_playing(data.r_playing);
+            });
+        },
+        ("/source/seek", Some(v)) => audio.lock().set_loc(v),
+        ("/resonant/seek", Some(v)) => r_audio.lock().set_loc(v),
+        ("/decay", Some(v)) => audio.lock().params.decay = v.max(0.0).min(1.0),
+        ("/volume", Some(v)) => audio.lock().params.volume = v.max(-40.0).min(6.0),
+        ("/transpose", Some(v)) => audio.lock().params.transpose = v,
+        ("/morph", Some(v)) => audio.lock().params.morph_pos = v.max(0.0).min(1.0),
+        ("/planner/max_peaks", Some(v)) => set_planner(sink, move |data| data.line_graph.planner.max_peaks = v.max(0.0).min(1.0)),
+        ("/planner/min_prominence", Some(v)) => set_planner(sink, move |data| data.line_graph.planner.min_prominence = v.max(0.0).min(1.0)),
+        ("/planner/min_threshold", Some(v)) => set_planner(sink, move |data| data.line_graph.planner.min_line = v.max(0.0).min(1.0)),
+        ("/planner/min_freq", Some(v)) => set_planner(sink, move |data| data.line_graph.planner.min_range = v.max(0.0).min(1.0)),
+        ("/planner/max_freq", Some(v)) => set_planner(sink, move |data| data.line_graph.planner.max_range = v.max(0.0).min(1.0)),
+        ("/build", _) => {
+            let _ = sink.add_idle_callback(|data: &mut AppState| crate::build_resonator(data));
+        },
+        _ => println!("Unhandled osc message {:?}", message),
+    }
+}
+
+// planner parameters live in the ui state so they are changed on the ui thread
+#[inline]
+fn set_planner<F: FnOnce(&mut AppState) + Send + 'static>(sink: &ExtEventSink, f: F) {
+    let _ = sink.add_idle_callback(f);
+}
@@ -0,0 +1,166 @@
+use resonator_builder::scaled_builder::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use crate::analysis::{compute_spectra, AnalysisSettings};
+
+pub const MAX_PEAKS: usize = 2000;
+pub const SPECTRUM_RESOLUTION: usize = 1000;
+// smallest selectable region in samples
+pub const MIN_REGION_LEN: usize = 1024;
+// planned peaks within this ratio of an excluded peak are left out of the plan
+const EXCLUDE_RATIO: f64 = 1.005;
+
+// the analysis and planner settings used to turn the resonant file into a plan,
+// edited by the graph in the ui through GraphData::planner
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PlannerSettings {
+    pub analysis: AnalysisSettings,
+    // plan the left and right channel separately instead of the mono mix
+    pub stereo: bool,
+    // values between 0.0 and 1.0
+    pub region_start: f64,
+    pub region_end: f64,
+    pub min_line: f64,
+    pub min_range: f64,
+    pub max_range: f64,
+    pub min_prominence: f64,
+    pub max_peaks: f64,
+    // args of peaks left out of the plan
+    pub excluded_peaks: Vec<f64>,
+}
+
+impl Default for PlannerSettings {
+    fn default() -> Self {
+        Self {
+            analysis: AnalysisSettings::default(),
+            stereo: false,
+            region_start: 0.0,
+            region_end: 1.0,
+            min_line: 0.0,
+            min_range: 0.0,
+            max_range: 0.5,
+            min_prominence: 0.3,
+            max_peaks: 0.1,
+            excluded_peaks: Vec::new(),
+        }
+    }
+}
+
+impl PlannerSettings {
+    #[inline]
+    pub fn is_excluded(&self, arg: f64) -> bool {
+        is_excluded(&self.excluded_peaks, arg)
+    }
+
+    // the scale and base of the spectrum the planner computes for one channel of audio, the threshold
+    // and prominence sliders are relative to it. in stereo the graph shares one scale between the
+    // channels, so each channel's own is needed
+    pub fn planner_scale(&self, audio: &[f64]) -> Result<(f64, f64), Box<dyn Error>> {
+        let (_, scale, base) = compute_spectra(&[audio], 1, &self.analysis)?;
+        Ok((scale, base))
+    }
+
+    // runs the planner on one channel of the selected region. scale is what planner_scale
+    // returned for the same audio
+    pub fn plan_audio(&self, audio: &[f64], scale: (f64, f64)) -> ScaledResonatorPlan {
+        let (spectrum_scale, spectrum_base) = scale;
+        if self.min_range >= self.max_range {
+            return ScaledResonatorPlan::empty();
+        }
+        // the audio is multiplied by the analysis window so the planner sees the same leakage as the graph
+        let audio = self.analysis.window.apply(audio);
+        let mut plan = ScaledResonatorPlanner::new()
+            .with_min_prominence(self.min_prominence * spectrum_scale)
+            .with_max_num_peaks((self.max_peaks * MAX_PEAKS as f64) as usize)
+            .with_min_freq(self.min_range)
+            .with_max_freq(self.max_range)
+            .with_min_threshold(self.min_line * spectrum_scale + spectrum_base)
+            .plan(&audio[..]);
+        plan.resonators.retain(|r| !self.is_excluded(r.0));
+        plan
+    }
+
+    // the spectra of the selected region of the resonant audio, as shown in the graph of the ui
+    pub fn analyze(&self, channels: &[Vec<f64>; 2]) -> Result<Analysis, Box<dyn Error>> {
+        let region = region_bounds(channels[0].len(), self.region_start, self.region_end);
+        let (left, right) = (&channels[0][region.0..region.1], &channels[1][region.0..region.1]);
+        let mono = left.iter().zip(right.iter()).map(|(l, r)| (l + r) / 2.0).collect::<Vec<f64>>();
+        if self.stereo {
+            // the mono mix is part of the analysis so the scale matches the graph
+            let (specs, spectrum_scale, spectrum_base) = compute_spectra(&[&mono[..], left, right], SPECTRUM_RESOLUTION, &self.analysis)?;
+            let mut specs = specs.into_iter();
+            Ok(Analysis {
+                spec: specs.next().unwrap(),
+                channel_specs: Some([specs.next().unwrap(), specs.next().unwrap()]),
+                spectrum_scale,
+                spectrum_base,
+                planner_scales: [self.planner_scale(left)?, self.planner_scale(right)?],
+                mono,
+                region,
+            })
+        } else {
+            let (mut specs, spectrum_scale, spectrum_base) = compute_spectra(&[&mono[..]], SPECTRUM_RESOLUTION, &self.analysis)?;
+            Ok(Analysis {
+                spec: specs.remove(0),
+                channel_specs: None,
+                spectrum_scale,
+                spectrum_base,
+                planner_scales: [(spectrum_scale, spectrum_base); 2],
+                mono,
+                region,
+            })
+        }
+    }
+
+    // the plan for each channel of the resonant audio, the mono plan twice when not planning in stereo
+    pub fn plan_channels(&self, channels: &[Vec<f64>; 2]) -> Result<(ScaledResonatorPlan, ScaledResonatorPlan), Box<dyn Error>> {
+        let analysis = self.analyze(channels)?;
+        Ok(self.plan_analyzed(channels, &analysis))
+    }
+
+    // plans from an analysis made with the same analysis settings and region, so only
+    // the planner settings can have changed since
+    pub fn plan_analyzed(&self, channels: &[Vec<f64>; 2], analysis: &Analysis) -> (ScaledResonatorPlan, ScaledResonatorPlan) {
+        let (start, end) = analysis.region;
+        let [left, right] = analysis.planner_scales;
+        if self.stereo {
+            (self.plan_audio(&channels[0][start..end], left), self.plan_audio(&channels[1][start..end], right))
+        } else {
+            let plan = self.plan_audio(&analysis.mono[..], left);
+            (plan.clone(), plan)
+        }
+    }
+}
+
+// the spectrum of the selected region, values between 0.0 and 1.0 over 0 to the nyquist frequency
+pub struct Analysis {
+    pub spec: Vec<f64>,
+    // the spectrum of each channel when planning in stereo
+    pub channel_specs: Option<[Vec<f64>; 2]>,
+    // the difference between the highest and lowest value in the spectrum
+    pub spectrum_scale: f64,
+    // the lowest value displayed in the spectrum
+    pub spectrum_base: f64,
+    // the scale and base of the spectrum the planner sees for each planned channel, see planner_scale
+    pub planner_scales: [(f64, f64); 2],
+    // the mono mix of the region
+    mono: Vec<f64>,
+    // the region in samples
+    region: (usize, usize),
+}
+
+#[inline]
+pub fn is_excluded(excluded_peaks: &[f64], arg: f64) -> bool {
+    excluded_peaks.iter().any(|v| (arg / v).ln().abs() < EXCLUDE_RATIO.ln())
+}
+
+// the sample range of a region given as values between 0.0 and 1.0, at least MIN_REGION_LEN long
+pub fn region_bounds(length: usize, start: f64, end: f64) -> (usize, usize) {
+    let mut start_idx = (start.max(0.0).min(1.0) * length as f64) as usize;
+    let mut end_idx = (end.max(0.0).min(1.0) * length as f64) as usize;
+    if end_idx < start_idx + MIN_REGION_LEN {
+        end_idx = (start_idx + MIN_REGION_LEN).min(length);
+        start_idx = end_idx.saturating_sub(MIN_REGION_LEN);
+    }
+    (start_idx, end_idx)
+}
@@ -0,0 +1,168 @@
+use druid::widget::prelude::*;
+use druid::{Color, Data, MouseButton, Rect};
+use druid::kurbo::{BezPath, Point};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use crate::state::AudioState;
+
+#[derive(Clone)]
+pub struct ProgressBar {
+    pub audio: Arc<Mutex<AudioState>>,
+}
+
+impl Data for ProgressBar {
+    fn same(&self, _other: &Self) -> bool {
+        // stub fow now
+        false
+    }
+}
+
+impl ProgressBar {
+    pub fn init(audio: Arc<Mutex<AudioState>>) -> Self {
+        Self {
+            audio,
+        }
+    }
+}
+
+// left click seeks, right drag (or shift + left drag) sets the loop region
+pub struct CustomProgressBar {
+    // start of the current loop drag and the position of the mouse, values between 0.0 and 1.0
+    loop_drag: Option<(f64, f64)>,
+}
+
+impl CustomProgressBar {
+    pub fn new() -> Self {
+        Self {
+            loop_drag: None,
+        }
+    }
+}
+
+impl Widget<ProgressBar> for CustomProgressBar {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ProgressBar, _env: &Env) {
+
+        match event {
+            Event::MouseDown(mouse_event) => {
+                let progress = (mouse_event.pos.x / ctx.size().width).max(0.0).min(1.0);
+                if mouse_event.button == MouseButton::Right || (mouse_event.button == MouseButton::Left && mouse_event.mods.shift()) {
+                    self.loop_drag = Some((progress, progress));
+                    ctx.set_active(true);
+                    ctx.set_handled();
+                } else if mouse_event.button == MouseButton::Left {
+                    let mut audio = data.audio.lock();
+                    audio.set_loc(progress);
+                    std::mem::drop(audio);
+                    ctx.set_handled();
+                }
+            },
+            Event::MouseMove(mouse_event) => {
+                if let Some((start, _)) = self.loop_drag {
+                    let progress = (mouse_event.pos.x / ctx.size().width).max(0.0).min(1.0);
+                    self.loop_drag = Some((start, progress));
+                    ctx.request_paint();
+                }
+            },
+            Event::MouseUp(_) => {
+                if let Some((start, end)) = self.loop_drag.take() {
+                    let mut audio = data.audio.lock();
+                    // a click without dragging loops the whole file again
+                    if (end - start).abs() < 0.005 {
+                        audio.set_loop_region(0.0, 1.0);
+                    } else {
+                        audio.set_loop_region(start.min(end), start.max(end));
+                    }
+                    std::mem::drop(audio);
+                    ctx.set_active(false);
+                    ctx.set_handled();
+                }
+            },
+            Event::WindowConnected => {
+                ctx.request_timer(std::time::Duration::from_secs_f64(1.0 / 60.0));
+            }
+            Event::Timer(_) => {
+                ctx.request_paint();
+                ctx.request_timer(std::time::Duration::from_secs_f64(1.0 / 60.0));
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &ProgressBar, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &ProgressBar, data: &ProgressBar, _env: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &ProgressBar, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ProgressBar, _env: &Env) {
+        let size = ctx.size();
+        let rect = Rect::from_origin_size((0.0, 0.0), size);
+        let audio = data.audio.lock();
+        let progress = audio.get_progress();
+        let looping = audio.looping;
+        let (loop_start, loop_end) = match self.loop_drag {
+            Some((a, b)) => (a.min(b), a.max(b)),
+            None => audio.get_loop_region(),
+        };
+        let overview = Arc::clone(&audio.overview);
+        let wet_overview = if audio.filter.is_some() {
+            Some(audio.wet_overview.clone())
+        } else {
+            None
+        };
+        std::mem::drop(audio);
+        ctx.fill(rect, &Color::grey(1.0));
+
+        // waveform overview, the played part in purple
+        let mid = size.height / 2.0;
+        let column_width = size.width / overview.len() as f64;
+        for (i, (min, max, rms)) in overview.iter().enumerate() {
+            let x = i as f64 * column_width;
+            let played = x < size.width * progress;
+            let (peak_color, rms_color) = if played {
+                (Color::rgb8(0xB5, 0xA3, 0xCC), Color::rgb8(0x7B, 0x61, 0x9E))
+            } else {
+                (Color::grey(0.8), Color::grey(0.6))
+            };
+            let peak = Rect::new(x, mid - *max as f64 * mid, x + column_width, mid - *min as f64 * mid);
+            ctx.fill(peak, &peak_color);
+            let rms = Rect::new(x, mid - *rms as f64 * mid, x + column_width, mid + *rms as f64 * mid);
+            ctx.fill(rms, &rms_color);
+        }
+
+        // resonated output on top
+        if let Some(wet_overview) = wet_overview {
+            let column_width = size.width / wet_overview.len() as f64;
+            for (i, peak) in wet_overview.iter().enumerate() {
+                let x = i as f64 * column_width;
+                let peak = (*peak as f64).min(1.0);
+                let wet = Rect::new(x, mid - peak * mid, x + column_width, mid + peak * mid);
+                ctx.fill(wet, &Color::rgba8(0x1e, 0xcb, 0xe1, 96));
+            }
+        }
+
+        // playhead
+        let mut path = BezPath::new();
+        path.move_to(Point::new(size.width * progress, 0.0));
+        path.line_to(Point::new(size.width * progress, size.height));
+        ctx.stroke(path, &Color::rgb8(0x7B, 0x61, 0x9E), 2.0);
+
+        let whole_file = loop_start <= 0.0 && loop_end >= 1.0;
+        if (looping && !whole_file) || self.loop_drag.is_some() {
+            let loop_rect = Rect::new(loop_start * size.width, 0.0, loop_end * size.width, size.height);
+            ctx.fill(loop_rect, &Color::rgba8(0x1e, 0xcb, 0xe1, 64));
+            for x in [loop_start, loop_end] {
+                let mut path = BezPath::new();
+                path.move_to(Point::new(x * size.width, 0.0));
+                path.line_to(Point::new(x * size.width, size.height));
+                ctx.stroke(path, &Color::rgb8(0x1e, 0xcb, 0xe1), 2.0);
+            }
+        }
+    }
+}
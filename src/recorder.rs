@@ -0,0 +1,305 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// a few seconds of stereo output, the writer thread drains it every WRITE_INTERVAL
+const RECORD_BUFFER_SIZE: usize = 1 << 19;
+const WRITE_INTERVAL: Duration = Duration::from_millis(20);
+const RECORDINGS_DIR: &str = "./recordings";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl RecordFormat {
+    pub const ALL: [RecordFormat; 3] = [RecordFormat::Int16, RecordFormat::Int24, RecordFormat::Float32];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordFormat::Int16 => "16",
+            RecordFormat::Int24 => "24",
+            RecordFormat::Float32 => "32f",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|f| f == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    fn spec(&self, channels: u16, sample_rate: u32) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            RecordFormat::Int16 => (16, SampleFormat::Int),
+            RecordFormat::Int24 => (24, SampleFormat::Int),
+            RecordFormat::Float32 => (32, SampleFormat::Float),
+        };
+        WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl Default for RecordFormat {
+    fn default() -> Self {
+        RecordFormat::Int24
+    }
+}
+
+// the audio thread's end of a recording, see stream::set_record_tap
+pub struct RecordTap {
+    producer: Producer<f32>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl RecordTap {
+    // called from the audio thread with the final (clamped) output buffer
+    #[inline]
+    pub fn write(&mut self, data: &[f32]) {
+        for v in data {
+            if self.producer.push(*v).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// what a recording was made from, written into the LIST/INFO chunk of the file
+pub struct RecordingInfo {
+    pub source: String,
+    pub resonant: String,
+    // the resonators of the source track's plan and the sample rate their args are relative to
+    pub plan: Option<(Vec<(f64, f64)>, Vec<(f64, f64)>)>,
+    pub plan_sample_rate: f64,
+}
+
+impl RecordingInfo {
+    fn comment(&self) -> String {
+        let mut comment = format!("source: {}\nresonant: {}\n", self.source, self.resonant);
+        match &self.plan {
+            Some((left, right)) => {
+                comment.push_str(&format!("plan (left): {}\n", format_plan(left, self.plan_sample_rate)));
+                comment.push_str(&format!("plan (right): {}\n", format_plan(right, self.plan_sample_rate)));
+            },
+            None => comment.push_str("plan: none\n"),
+        }
+        comment
+    }
+}
+
+// resonators as "freq_hz@amp" separated by spaces
+fn format_plan(resonators: &[(f64, f64)], sample_rate: f64) -> String {
+    resonators
+        .iter()
+        .map(|(arg, amp)| format!("{:.2}@{:.4}", arg / (2.0 * std::f64::consts::PI) * sample_rate, amp))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// a wav file being written by a writer thread
+pub struct Recording {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    dropped: Arc<AtomicUsize>,
+    writer: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Recording {
+    // starts a writer thread for the file and returns the tap the audio thread should feed
+    pub fn start<P: AsRef<Path>>(path: P, format: RecordFormat, channels: u16, sample_rate: u32, info: RecordingInfo) -> Result<(Self, RecordTap), Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let writer = WavWriter::create(&path, format.spec(channels, sample_rate))?;
+        let (producer, consumer) = RingBuffer::new(RECORD_BUFFER_SIZE);
+        let stop = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let thread_stop = Arc::clone(&stop);
+        let thread_path = path.clone();
+        let handle = std::thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || {
+                run_writer(writer, consumer, format, &thread_stop).map_err(|e| e.to_string())?;
+                append_info_chunk(&thread_path, &info).map_err(|e| e.to_string())
+            })?;
+
+        let tap = RecordTap {
+            producer,
+            dropped: Arc::clone(&dropped),
+        };
+        Ok((Self { path, stop, dropped, writer: Some(handle) }, tap))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // the tap must already be removed from the stream so everything left in the buffer gets written
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.stop.store(true, Ordering::Release);
+        let handle = match self.writer.take() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        handle.join().map_err(|_| "Recorder thread panicked")??;
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            println!("Recording {} is missing {} samples, the writer couldn't keep up", self.path.display(), dropped);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            println!("Error occurred while finishing recording: {:?}", e);
+        }
+    }
+}
+
+#[inline]
+fn write_sample<W: std::io::Write + std::io::Seek>(writer: &mut WavWriter<W>, format: RecordFormat, v: f32) -> Result<(), hound::Error> {
+    match format {
+        RecordFormat::Int16 => writer.write_sample((v.max(-1.0).min(1.0) * i16::MAX as f32) as i16),
+        RecordFormat::Int24 => writer.write_sample((v.max(-1.0).min(1.0) * 8_388_607.0) as i32),
+        RecordFormat::Float32 => writer.write_sample(v),
+    }
+}
+
+// writes interleaved samples to a wav file in one go, for offline rendering
+pub fn write_wav<P: AsRef<Path>>(path: P, format: RecordFormat, channels: u16, sample_rate: u32, samples: &[f32]) -> Result<(), Box<dyn Error>> {
+    let mut writer = WavWriter::create(path, format.spec(channels, sample_rate))?;
+    for v in samples {
+        write_sample(&mut writer, format, *v)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn run_writer<W: std::io::Write + std::io::Seek>(mut writer: WavWriter<W>, mut consumer: Consumer<f32>, format: RecordFormat, stop: &AtomicBool) -> Result<(), hound::Error> {
+    loop {
+        // read the flag before draining so nothing pushed before the stop is lost
+        let stopping = stop.load(Ordering::Acquire);
+        while let Ok(v) = consumer.pop() {
+            write_sample(&mut writer, format, v)?;
+        }
+        if stopping {
+            break;
+        }
+        std::thread::sleep(WRITE_INTERVAL);
+    }
+    writer.finalize()
+}
+
+// hound doesn't write metadata, so the LIST/INFO chunk is appended after the file is finalized
+fn append_info_chunk(path: &Path, info: &RecordingInfo) -> std::io::Result<()> {
+    let fields = [
+        (b"ISFT", "CapstoneAudioDemo".to_string()),
+        (b"ISRC", info.source.clone()),
+        (b"ICMT", info.comment()),
+    ];
+    let mut chunk = b"INFO".to_vec();
+    for (id, text) in fields.iter() {
+        let mut text = text.clone().into_bytes();
+        text.push(0);
+        chunk.extend_from_slice(*id);
+        chunk.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&text);
+        if text.len() % 2 == 1 {
+            chunk.push(0);
+        }
+    }
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut len = file.seek(SeekFrom::End(0))?;
+    // chunks start on even offsets
+    if len % 2 == 1 {
+        file.write_all(&[0])?;
+        len += 1;
+    }
+    file.write_all(b"LIST")?;
+    file.write_all(&(chunk.len() as u32).to_le_bytes())?;
+    file.write_all(&chunk)?;
+    len += 8 + chunk.len() as u64;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&((len - 8) as u32).to_le_bytes())?;
+    Ok(())
+}
+
+// starts and stops recordings of the stream output
+pub struct Recorder {
+    pub source: String,
+    pub resonant: String,
+    // format of the output stream
+    pub channels: u16,
+    pub sample_rate: u32,
+    current: Option<Recording>,
+}
+
+impl Recorder {
+    pub fn new(source: String, resonant: String, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            source,
+            resonant,
+            channels,
+            sample_rate,
+            current: None,
+        }
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // stops any recording in progress and starts a new one
+    pub fn start<P: AsRef<Path>>(&mut self, path: P, format: RecordFormat, plan: Option<&(ScaledResonatorPlan, ScaledResonatorPlan)>, plan_sample_rate: f64) -> Result<(), Box<dyn Error>> {
+        self.stop()?;
+        let info = RecordingInfo {
+            source: self.source.clone(),
+            resonant: self.resonant.clone(),
+            plan: plan.map(|(left, right)| (left.resonators.clone(), right.resonators.clone())),
+            plan_sample_rate,
+        };
+        let (recording, tap) = Recording::start(path, format, self.channels, self.sample_rate, info)?;
+        crate::stream::set_record_tap(Some(tap));
+        println!("Recording to {}", recording.path().display());
+        self.current = Some(recording);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut recording = match self.current.take() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        crate::stream::set_record_tap(None);
+        recording.finish()?;
+        println!("Saved recording {}", recording.path().display());
+        Ok(())
+    }
+}
+
+// a new file in ./recordings named after the current time
+pub fn default_recording_path() -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(RECORDINGS_DIR)?;
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    Ok(Path::new(RECORDINGS_DIR).join(format!("recording-{}.wav", secs)))
+}
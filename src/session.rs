@@ -0,0 +1,239 @@
+use druid::{Point, Size, WindowHandle};
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use crate::AppState;
+use crate::args::Args;
+use crate::automation::{Automation, AutomationMode};
+use crate::morph::MorphPlan;
+use crate::planner::PlannerSettings;
+use crate::state::{AudioState, TrackParams};
+
+pub const DEFAULT_SESSION_PATH: &str = "./session.json";
+// bumped when a change would make older session files load differently
+const SESSION_VERSION: u32 = 1;
+
+// everything needed to bring the app back to where it was. the parameters are the same
+// structs the tracks and the graph hold (AudioState::params and GraphData::planner), the
+// app state is built from the tracks loaded from this (see AppState::from_session)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub source: TrackSession,
+    pub resonant: TrackSession,
+    pub planner: PlannerSettings,
+    // the plan the source track's resonator was built from
+    pub plan: Option<SavedPlan>,
+    // the plans stored in morph slots A and B
+    #[serde(default)]
+    pub morph_slots: [Option<SavedPlan>; 2],
+    // the plan imported from a file or spec, used instead of the planner, and its ring time
+    // in seconds, see GraphData::imported
+    #[serde(default)]
+    pub imported: Option<SavedPlan>,
+    #[serde(default)]
+    pub imported_decay: Option<f64>,
+    pub window: Option<WindowLayout>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrackSession {
+    pub path: String,
+    pub playing: bool,
+    // playback position in samples
+    pub loc: usize,
+    pub looping: bool,
+    // values between 0.0 and 1.0
+    pub loop_region: (f64, f64),
+    // kept at the top level of the track so older sessions load, the fields added
+    // since then are missing from them and get their defaults
+    #[serde(flatten)]
+    pub params: TrackParams,
+    #[serde(default)]
+    pub automation_mode: AutomationMode,
+    // the lanes the mode plays back, None when nothing was recorded
+    #[serde(default)]
+    pub automation: Option<Automation>,
+}
+
+// (arg, amplitude) of every resonator of each channel
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedPlan {
+    pub left: Vec<(f64, f64)>,
+    pub right: Vec<(f64, f64)>,
+    pub region: Option<(f64, f64)>,
+    // the morph the plan was built from, see AudioState::morph
+    #[serde(default)]
+    pub morph: Option<(MorphPlan, MorphPlan)>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub size: (f64, f64),
+    pub position: (f64, f64),
+}
+
+impl TrackSession {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            playing: false,
+            loc: 0,
+            looping: true,
+            loop_region: (0.0, 1.0),
+            params: TrackParams::default(),
+            automation_mode: AutomationMode::Off,
+            automation: None,
+        }
+    }
+
+    fn of(audio_state: &AudioState) -> Self {
+        Self {
+            path: audio_state.path.clone(),
+            playing: audio_state.playing,
+            loc: audio_state.get_loc(),
+            looping: audio_state.looping,
+            loop_region: audio_state.get_loop_region(),
+            params: audio_state.params.clone(),
+            automation_mode: audio_state.automation.mode(),
+            automation: Some(audio_state.automation.clone()).filter(|automation| !automation.is_empty()),
+        }
+    }
+
+    // loads the track's file and puts it in the saved state
+    pub fn load_audio_state(&self) -> Result<AudioState, Box<dyn Error>> {
+        let mut audio_state = AudioState::init_audio_state(&self.path)?;
+        audio_state.playing = self.playing;
+        audio_state.looping = self.looping;
+        audio_state.set_loop_region(self.loop_region.0, self.loop_region.1);
+        audio_state.set_loc_samples(self.loc);
+        audio_state.params = self.params.clone();
+        if let Some(automation) = &self.automation {
+            audio_state.automation = automation.clone();
+            audio_state.automation.rescale(audio_state.sample_rate);
+        }
+        audio_state.automation.set_mode(self.automation_mode);
+        Ok(audio_state)
+    }
+}
+
+impl SavedPlan {
+    fn of(audio_state: &AudioState) -> Option<Self> {
+        let (plan1, plan2) = audio_state.plan.as_ref()?;
+        Some(Self {
+            region: audio_state.plan_region,
+            morph: audio_state.morph.clone(),
+            ..Self::of_plans(plan1, plan2)
+        })
+    }
+
+    // a plan for each channel, e.g. of a morph slot
+    pub fn of_plans(plan1: &ScaledResonatorPlan, plan2: &ScaledResonatorPlan) -> Self {
+        Self {
+            left: plan1.resonators.clone(),
+            right: plan2.resonators.clone(),
+            region: None,
+            morph: None,
+        }
+    }
+
+    pub fn plans(&self) -> (ScaledResonatorPlan, ScaledResonatorPlan) {
+        let mut plan1 = ScaledResonatorPlan::empty();
+        plan1.resonators = self.left.clone();
+        let mut plan2 = ScaledResonatorPlan::empty();
+        plan2.resonators = self.right.clone();
+        (plan1, plan2)
+    }
+
+    // rebuilds the resonator array of the plan, keeping the decay, transpose and morph position
+    // the track was loaded with
+    pub fn restore(&self, audio_state: &mut AudioState) -> Result<(), Box<dyn Error>> {
+        audio_state.set_plan(self.plans(), self.region)?;
+        audio_state.morph = self.morph.clone();
+        // apply the saved parameters to the new arrays on the first buffer
+        audio_state.old_decay = f64::NAN;
+        audio_state.old_transpose = f64::NAN;
+        audio_state.old_morph_pos = f64::NAN;
+        Ok(())
+    }
+}
+
+impl WindowLayout {
+    pub fn of(window: &WindowHandle) -> Self {
+        let size = window.get_size();
+        let position = window.get_position();
+        Self {
+            size: (size.width, size.height),
+            position: (position.x, position.y),
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> Size {
+        Size::new(self.size.0, self.size.1)
+    }
+
+    #[inline]
+    pub fn position(&self) -> Point {
+        Point::new(self.position.0, self.position.1)
+    }
+}
+
+impl Session {
+    // a fresh session for the files and analysis settings given on the command line
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            version: SESSION_VERSION,
+            source: TrackSession::new(&args.audio_path),
+            resonant: TrackSession::new(&args.r_audio_path),
+            planner: PlannerSettings {
+                analysis: args.analysis,
+                stereo: args.stereo,
+                ..PlannerSettings::default()
+            },
+            plan: None,
+            morph_slots: [None, None],
+            imported: None,
+            imported_decay: None,
+            window: None,
+        }
+    }
+
+    pub fn capture(data: &AppState, window: Option<WindowLayout>) -> Self {
+        let audio_state = data.audio_state.lock();
+        let source = TrackSession::of(&audio_state);
+        let plan = SavedPlan::of(&audio_state);
+        std::mem::drop(audio_state);
+        let resonant = TrackSession::of(&data.r_audio_state.lock());
+        let slots = data.morph_slots.lock();
+        let saved = |slot: &Option<(ScaledResonatorPlan, ScaledResonatorPlan)>| slot.as_ref().map(|(plan1, plan2)| SavedPlan::of_plans(plan1, plan2));
+        let morph_slots = [saved(&slots[0]), saved(&slots[1])];
+        std::mem::drop(slots);
+        Self {
+            version: SESSION_VERSION,
+            source,
+            resonant,
+            planner: data.line_graph.planner.clone(),
+            plan,
+            morph_slots,
+            imported: data.line_graph.imported.as_ref().map(|plans| SavedPlan::of_plans(&plans.0, &plans.1)),
+            imported_decay: data.line_graph.imported_decay,
+            window,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let session: Session = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if session.version > SESSION_VERSION {
+            return Err(format!("Session version {} is newer than this app supports ({})", session.version, SESSION_VERSION).into());
+        }
+        Ok(session)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+// an override applies to resonators within this ratio of its frequency, a sixth of a semitone
+const OVERRIDE_RATIO: f64 = 1.01;
+
+// a change to a single peak of the plan, applied after the curves
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PeakOverride {
+    // hz
+    pub freq: f64,
+    // db added to the peak
+    #[serde(default)]
+    pub gain: Option<f64>,
+    // factor of the peak's ring time
+    #[serde(default)]
+    pub decay: Option<f64>,
+}
+
+// gain and ring time of each resonator by its frequency in the plan. the tilts pivot around
+// pivot, the curves and overrides come on top of them. e.g. a decay_tilt of 0.7 makes every
+// octave ring 0.7 times as long as the one below it, like a bell
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShapingSettings {
+    // hz
+    pub pivot: f64,
+    // factor of the ring time per octave above the pivot
+    pub decay_tilt: f64,
+    // db per octave above the pivot
+    pub gain_tilt: f64,
+    // (freq in hz, factor of the ring time) points, interpolated over log frequency and held past the ends
+    pub decay_curve: Vec<(f64, f64)>,
+    // (freq in hz, db) points of an eq curve, interpolated the same way
+    pub gain_curve: Vec<(f64, f64)>,
+    pub overrides: Vec<PeakOverride>,
+}
+
+impl Default for ShapingSettings {
+    fn default() -> Self {
+        Self {
+            pivot: 440.0,
+            decay_tilt: 1.0,
+            gain_tilt: 0.0,
+            decay_curve: Vec::new(),
+            gain_curve: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl ShapingSettings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut settings: ShapingSettings = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if settings.pivot <= 0.0 || settings.decay_tilt <= 0.0 {
+            return Err("The shaping pivot and decay tilt must be above zero".into());
+        }
+        settings.sort_curves();
+        Ok(settings)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // curve_at looks the points up by frequency. settings that don't come from load, e.g. from a
+    // session, are sorted by AudioState::apply_shaping
+    pub fn sort_curves(&mut self) {
+        self.decay_curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.gain_curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    // whether any resonator's gain is changed
+    #[inline]
+    pub fn shapes_gain(&self) -> bool {
+        self.gain_tilt != 0.0 || !self.gain_curve.is_empty() || self.overrides.iter().any(|o| o.gain.is_some())
+    }
+
+    // whether any resonator's ring time is changed, otherwise the array can share one decay
+    #[inline]
+    pub fn shapes_decay(&self) -> bool {
+        self.decay_tilt != 1.0 || !self.decay_curve.is_empty() || self.overrides.iter().any(|o| o.decay.is_some())
+    }
+
+    // (linear gain, ring time factor) of a resonator at freq hz
+    pub fn factors_at(&self, freq: f64) -> (f64, f64) {
+        let octaves = (freq.max(1e-3) / self.pivot.max(1e-3)).log2();
+        let mut db = self.gain_tilt * octaves + curve_at(&self.gain_curve, freq).unwrap_or(0.0);
+        let mut decay = self.decay_tilt.max(0.0).powf(octaves) * curve_at(&self.decay_curve, freq).unwrap_or(1.0);
+        for o in self.overrides.iter().filter(|o| (freq / o.freq).ln().abs() < OVERRIDE_RATIO.ln()) {
+            db += o.gain.unwrap_or(0.0);
+            decay *= o.decay.unwrap_or(1.0);
+        }
+        (10_f64.powf(db / 20.0), decay.max(0.0))
+    }
+
+    // the factors for every resonator of a plan, in the plan's order
+    pub fn factors(&self, resonators: &[(f64, f64)], sample_rate: f64) -> Vec<(f64, f64)> {
+        resonators
+            .iter()
+            .map(|(arg, _)| self.factors_at(arg / (2.0 * PI) * sample_rate))
+            .collect()
+    }
+}
+
+// the value of a breakpoint curve at freq, interpolated over log frequency
+fn curve_at(points: &[(f64, f64)], freq: f64) -> Option<f64> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if freq <= first.0 {
+        return Some(first.1);
+    }
+    if freq >= last.0 {
+        return Some(last.1);
+    }
+    let i = points.partition_point(|p| p.0 <= freq);
+    let (a, b) = (points[i - 1], points[i]);
+    let t = (freq / a.0).ln() / (b.0 / a.0).ln();
+    Some(a.1 + (b.1 - a.1) * t)
+}
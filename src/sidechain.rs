@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::level::LevelMeter;
+
+// levels below this many db under full scale don't move the decay
+const RANGE_DB: f64 = 60.0;
+// shortest attack or release in seconds, keeps the follower from jumping within a block
+const MIN_TIME: f64 = 0.0005;
+
+// what the envelope follower listens to
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum SidechainKey {
+    Off,
+    // the audio going into the track's resonators
+    Input,
+    // the level of the other track, e.g. the resonant track for the source track
+    Other,
+}
+
+impl SidechainKey {
+    pub const ALL: [SidechainKey; 3] = [SidechainKey::Off, SidechainKey::Input, SidechainKey::Other];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SidechainKey::Off => "off",
+            SidechainKey::Input => "input",
+            SidechainKey::Other => "other track",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|k| k == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SidechainSettings {
+    pub key: SidechainKey,
+    // seconds for the envelope to rise and fall by about two thirds
+    pub attack: f64,
+    pub release: f64,
+    // how far a full scale key moves the decay, 0.0 to 1.0 like the decay itself
+    pub depth: f64,
+    // loud passages ring longer and quiet ones are damped instead
+    pub invert: bool,
+}
+
+impl SidechainSettings {
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.key != SidechainKey::Off && self.depth != 0.0
+    }
+}
+
+impl Default for SidechainSettings {
+    fn default() -> Self {
+        Self {
+            key: SidechainKey::Off,
+            attack: 0.01,
+            release: 0.3,
+            depth: 0.5,
+            invert: false,
+        }
+    }
+}
+
+// an envelope follower moving the decay of a track's resonators with the level of its key.
+// by default quiet passages ring longer and loud passages are damped. the settings are kept
+// with the track's other parameters and passed in
+#[derive(Default)]
+pub struct Sidechain {
+    // the other track's meter for SidechainKey::Other, set by whoever owns both tracks.
+    // it holds the level of the other track's last block, cleared to silence while that track isn't played
+    pub other: Option<Arc<LevelMeter>>,
+    // linear rms level
+    envelope: f64,
+}
+
+impl Sidechain {
+    // moves the envelope toward the rms level of the next frames of the key. input is the track's
+    // own input for those frames, as one slice per channel
+    #[inline]
+    pub fn follow(&mut self, settings: &SidechainSettings, input: [&[f64]; 2], sample_rate: f64) {
+        let frames = input[0].len();
+        if frames == 0 {
+            return;
+        }
+        let level = match settings.key {
+            SidechainKey::Off => 0.0,
+            SidechainKey::Input => {
+                let sum_sq = input[0].iter().chain(input[1].iter()).map(|v| v * v).sum::<f64>();
+                (sum_sq / (2 * frames) as f64).sqrt()
+            },
+            SidechainKey::Other => self.other.as_ref().map(|m| m.rms() as f64).unwrap_or(0.0),
+        };
+        let time = if level > self.envelope { settings.attack } else { settings.release };
+        let coeff = (-(frames as f64) / (time.max(MIN_TIME) * sample_rate)).exp();
+        self.envelope = level + (self.envelope - level) * coeff;
+    }
+
+    // the envelope between 0.0 (RANGE_DB below full scale or quieter) and 1.0 (full scale)
+    #[inline]
+    pub fn amount(&self) -> f64 {
+        if self.envelope <= 0.0 {
+            return 0.0;
+        }
+        (1.0 + 20.0 * self.envelope.log10() / RANGE_DB).max(0.0).min(1.0)
+    }
+
+    // the decay the resonators get for the decay slider's value
+    #[inline]
+    pub fn modulate(&self, settings: &SidechainSettings, decay: f64) -> f64 {
+        let offset = settings.depth * self.amount();
+        let decay = if settings.invert { decay + offset } else { decay - offset };
+        decay.max(0.0).min(1.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
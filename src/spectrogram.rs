@@ -0,0 +1,124 @@
+use druid::widget::prelude::*;
+use druid::{Color, Rect, MouseButton};
+use druid::piet::{ImageFormat, InterpolationMode};
+use crate::graph::GraphData;
+
+// a custom widget that draws the stft of the resonant file and lets the user
+// drag to select the time region the plan is built from
+pub struct Spectrogram {
+    // start of the current drag and the position of the mouse, values between 0.0 and 1.0
+    drag: Option<(f64, f64)>,
+}
+
+impl Spectrogram {
+    pub fn new() -> Self {
+        Self {
+            drag: None,
+        }
+    }
+}
+
+// maps a value between 0.0 and 1.0 to a black -> purple -> cyan -> white ramp
+#[inline]
+fn heat_color(v: f64) -> [u8; 3] {
+    let stops: [[f64; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [123.0, 97.0, 158.0],
+        [30.0, 203.0, 225.0],
+        [255.0, 255.0, 255.0],
+    ];
+    let v = v.max(0.0).min(1.0) * (stops.len() - 1) as f64;
+    let i = (v as usize).min(stops.len() - 2);
+    let t = v - i as f64;
+    let mut out = [0; 3];
+    for c in 0..3 {
+        out[c] = (stops[i][c] * (1.0 - t) + stops[i + 1][c] * t) as u8;
+    }
+    out
+}
+
+impl Widget<GraphData> for Spectrogram {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut GraphData, _env: &Env) {
+        match event {
+            Event::MouseDown(mouse_event) => {
+                if mouse_event.button == MouseButton::Left {
+                    let x = (mouse_event.pos.x / ctx.size().width).max(0.0).min(1.0);
+                    self.drag = Some((x, x));
+                    ctx.set_active(true);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                }
+            },
+            Event::MouseMove(mouse_event) => {
+                if let Some((start, _)) = self.drag {
+                    let x = (mouse_event.pos.x / ctx.size().width).max(0.0).min(1.0);
+                    self.drag = Some((start, x));
+                    ctx.request_paint();
+                }
+            },
+            Event::MouseUp(mouse_event) => {
+                if mouse_event.button == MouseButton::Left {
+                    if let Some((start, end)) = self.drag.take() {
+                        // a click without dragging selects the whole file again
+                        let (start, end) = if (end - start).abs() < 0.005 {
+                            (0.0, 1.0)
+                        } else {
+                            (start.min(end), start.max(end))
+                        };
+                        if let Err(e) = data.select_region(start, end) {
+                            println!("Error occurred while analyzing selected region: {:?}", e);
+                        }
+                    }
+                    ctx.set_active(false);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &GraphData, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &GraphData, data: &GraphData, _env: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &GraphData, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &GraphData, _env: &Env) {
+        let size = ctx.size();
+        let bg = Rect::new(0.0, 0.0, size.width, size.height);
+        ctx.fill(bg, &Color::rgb8(0, 0, 0));
+
+        let columns = data.spectrogram.len();
+        let rows = data.spectrogram.first().map(|c| c.len()).unwrap_or(0);
+        if columns > 0 && rows > 0 {
+            // low frequencies at the bottom of the image
+            let mut buf = Vec::with_capacity(columns * rows * 3);
+            for row in (0..rows).rev() {
+                for col in 0..columns {
+                    buf.extend_from_slice(&heat_color(data.spectrogram[col][row]));
+                }
+            }
+            match ctx.make_image(columns, rows, &buf[..], ImageFormat::Rgb) {
+                Ok(image) => ctx.draw_image(&image, bg, InterpolationMode::Bilinear),
+                Err(e) => println!("Error occurred while drawing spectrogram: {:?}", e),
+            }
+        }
+
+        let (start, end) = match self.drag {
+            Some((a, b)) => (a.min(b), a.max(b)),
+            None => (data.planner.region_start, data.planner.region_end),
+        };
+        let shade = Color::rgba8(0, 0, 0, 160);
+        ctx.fill(Rect::new(0.0, 0.0, start * size.width, size.height), &shade);
+        ctx.fill(Rect::new(end * size.width, 0.0, size.width, size.height), &shade);
+        let grey = Color::grey(0.8);
+        ctx.stroke(Rect::new(start * size.width, 0.0, end * size.width, size.height), &grey, 2.0);
+    }
+}
@@ -1,96 +1,311 @@
 use std::path::Path;
 use std::error::Error;
+use std::sync::Arc;
 use cpal::{Sample, FromSample};
 use gp_resonator::{resonator_array::ConjPoleResonatorArray, resonator::ConjPoleResonator};
 use resonator_builder::scaled_builder::ScaledResonatorPlan;
 
-use crate::load_audio;
+use crate::audio::load_audio;
+use crate::morph::MorphPlan;
+use crate::level::LevelMeter;
+use crate::automation::{Automation, AutomationParam};
+use crate::modulation::{Modulation, ModulationSettings};
+use crate::shaping::ShapingSettings;
+use crate::sidechain::{Sidechain, SidechainSettings};
+use serde::{Deserialize, Serialize};
+
+// length of the crossfade at the loop seam in samples
+const LOOP_CROSSFADE: usize = 256;
+// number of columns in the waveform overview
+pub const OVERVIEW_BUCKETS: usize = 1024;
+// the decay a newly built resonator starts with, log10(2)
+pub const DEFAULT_DECAY: f64 = 0.3010299956639812;
+// frames between decay updates while the sidechain moves it
+const SIDECHAIN_BLOCK: usize = 128;
+
+// what the sliders and settings files set on a track. saved with a session as it is,
+// see session::TrackSession
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackParams {
+    pub decay: f64,
+    // octaves
+    pub transpose: f64,
+    // db
+    pub volume: f64,
+    // between the two morph plans, 0.0 to 1.0
+    pub morph_pos: f64,
+    // moves the decay with the level of the input or the other track
+    pub sidechain: SidechainSettings,
+    // lfos and envelopes added to decay, transpose and volume. only run while the track has resonators
+    pub modulation: ModulationSettings,
+    // gain and decay of each resonator by its frequency
+    pub shaping: ShapingSettings,
+}
+
+impl Default for TrackParams {
+    fn default() -> Self {
+        Self {
+            decay: 1.0,
+            transpose: 0.0,
+            volume: 0.0,
+            morph_pos: 0.0,
+            sidechain: SidechainSettings::default(),
+            modulation: ModulationSettings::default(),
+            shaping: ShapingSettings::default(),
+        }
+    }
+}
 
 pub struct AudioState {
+    // the file the audio was loaded from
+    pub path: String,
     audio: [Vec<f32>; 2],
     pub playing: bool,
     loc: usize,
 
+    // (min, max, rms) of the mono mix for each overview bucket, computed once at load
+    pub overview: Arc<Vec<(f32, f32, f32)>>,
+    // peak of the resonated output for each overview bucket, filled in while playing
+    pub wet_overview: Vec<f32>,
+    wet_bucket: usize,
+
+    // loop between loop_start and loop_end (in samples), otherwise stop at the end of the file
+    pub looping: bool,
+    loop_start: usize,
+    loop_end: usize,
+
+    // decay, transpose, volume and the rest of what the sliders set, saved with a session
+    pub params: TrackParams,
+
     pub filter: Option<(ConjPoleResonatorArray, ConjPoleResonatorArray)>,
-    pub decay: f64,
     pub old_decay: f64,
 
-    pub plan: Option<ScaledResonatorPlan>,
-    pub transpose: f64,
+    // one plan per channel, matching filter
+    pub plan: Option<(ScaledResonatorPlan, ScaledResonatorPlan)>,
+    // the time region of the resonant file the plan was built from
+    pub plan_region: Option<(f64, f64)>,
     pub old_transpose: f64,
 
+    // when set, the filter moves between two plans according to params.morph_pos
+    pub morph: Option<(MorphPlan, MorphPlan)>,
+    pub old_morph_pos: f64,
+
     pub sample_rate: f64,
     pub limiter_scale: f64,
-    pub volume: f64,
+
+    // linear gain of the audio that excites the filter, set by midi velocity
+    pub input_gain: f64,
+    pub old_input_gain: f64,
+
+    // levels of what this track adds to the output
+    pub meter: Arc<LevelMeter>,
+
+    // recorded slider moves for decay, volume, transpose and the morph position
+    pub automation: Automation,
+
+    // the envelope follower of params.sidechain
+    pub sidechain: Sidechain,
+    // the running lfos and envelopes of params.modulation
+    pub modulation: Modulation,
+
+    // the shaping the factors were last computed for, see apply_shaping
+    old_shaping: Option<ShapingSettings>,
+    // (linear gain, ring time factor) of every resonator of each channel for old_shaping
+    shaping_factors: [Vec<(f64, f64)>; 2],
 }
 
 impl AudioState {
     #[inline]
     pub fn init_audio_state<P: AsRef<Path>>(path: P) -> Result<AudioState, Box<dyn Error>> {
-        let (audio, sample_rate) = load_audio(path)?;
-        Ok(
-            AudioState {
-                audio,
-                playing: false,
-                loc: 0,
-                filter: None,
-                sample_rate,
-                decay: 1.0,
-                old_decay: 1.0,
-                plan: None,
-                transpose: 0.0,
-                old_transpose: 0.0,
-                limiter_scale: 0.0,
-                volume: 0.0,
+        let (audio, sample_rate) = load_audio(&path)?;
+        Ok(Self::from_samples(path.as_ref().to_string_lossy().to_string(), audio, sample_rate))
+    }
+
+    // a track playing the given samples instead of a file
+    pub fn from_samples(path: String, audio: [Vec<f32>; 2], sample_rate: f64) -> AudioState {
+        let length = audio[0].len();
+        let overview = compute_overview(&audio, OVERVIEW_BUCKETS);
+        AudioState {
+            path,
+            audio,
+            playing: false,
+            loc: 0,
+            overview: Arc::new(overview),
+            wet_overview: vec![0.0; OVERVIEW_BUCKETS],
+            wet_bucket: usize::MAX,
+            looping: true,
+            loop_start: 0,
+            loop_end: length,
+            params: TrackParams::default(),
+            filter: None,
+            sample_rate,
+            old_decay: 1.0,
+            plan: None,
+            plan_region: None,
+            old_transpose: 0.0,
+            morph: None,
+            old_morph_pos: 0.0,
+            limiter_scale: 0.0,
+            input_gain: 1.0,
+            old_input_gain: 1.0,
+            meter: Arc::new(LevelMeter::new()),
+            automation: Automation::new(sample_rate),
+            sidechain: Sidechain::default(),
+            modulation: Modulation::default(),
+            old_shaping: None,
+            shaping_factors: [Vec::new(), Vec::new()],
+        }
+    }
+
+    // a track without a file that only processes the input given to add_audio_with_input, e.g. in a plugin.
+    // it holds a single silent frame since the overview and automation need a length
+    pub fn for_input(sample_rate: f64) -> AudioState {
+        Self::from_samples(String::new(), [vec![0.0], vec![0.0]], sample_rate)
+    }
+
+    // builds the resonator arrays of a plan for each channel and swaps them in. the decay, transpose
+    // and morph position are left to the caller, set the old_ values to NaN to apply them to the new arrays
+    pub fn set_plan(&mut self, plans: (ScaledResonatorPlan, ScaledResonatorPlan), region: Option<(f64, f64)>) -> Result<(), Box<dyn Error>> {
+        let array1 = plans.0.build_resonator_array(self.sample_rate).map_err(|e| format!("{:?}", e))?;
+        let array2 = plans.1.build_resonator_array(self.sample_rate).map_err(|e| format!("{:?}", e))?;
+        self.filter = Some((array1, array2));
+        self.plan = Some(plans);
+        self.plan_region = region;
+        self.old_shaping = None;
+        self.apply_shaping();
+        Ok(())
+    }
+
+    // the left and right channel of the track
+    #[inline]
+    pub fn samples(&self) -> &[Vec<f32>; 2] {
+        &self.audio
+    }
+
+    // reads the frame at loc and advances, wrapping at the loop end or stopping at the end of the file.
+    // returns None once a one-shot has finished
+    #[inline]
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        // the rest of the buffer after a one-shot ends is silence
+        if !self.playing {
+            return None;
+        }
+        if !self.looping && self.loc >= self.audio[0].len() {
+            self.playing = false;
+            self.loc = 0;
+            self.modulation.release();
+            return None;
+        }
+        if self.looping && self.loc >= self.loop_end {
+            // e.g. after seeking past the loop, the end of the file must not be read past
+            self.loc = self.loop_start;
+        }
+        let mut frame = [self.audio[0][self.loc], self.audio[1][self.loc]];
+        if self.looping {
+            // fade the end of the loop into its start so the seam doesn't click, then skip the faded part
+            let fade = LOOP_CROSSFADE.min((self.loop_end - self.loop_start) / 2);
+            let fade_start = self.loop_end - fade;
+            if fade > 0 && self.loc >= fade_start && self.loc < self.loop_end {
+                let offset = self.loc - fade_start;
+                let t = offset as f32 / fade as f32;
+                for c in 0..2 {
+                    frame[c] = frame[c] * (1.0 - t) + self.audio[c][self.loop_start + offset] * t;
+                }
             }
-        )
+            self.loc += 1;
+            if self.loc >= self.loop_end {
+                self.loc = self.loop_start + fade;
+                self.modulation.looped(&self.params.modulation);
+            }
+        } else {
+            self.loc += 1;
+        }
+        Some(frame)
+    }
+
+    // the next frame of the file, silence once it's stopped, plus frame i of the input
+    #[inline]
+    fn next_frame_with_input(&mut self, input: Option<&[f32]>, i: usize) -> [f32; 2] {
+        let [l, r] = self.next_frame().unwrap_or([0.0; 2]);
+        match input {
+            Some(input) => [l + input[2 * i], r + input[2 * i + 1]],
+            None => [l, r],
+        }
     }
 
     #[inline]
     pub fn write_audio<T: Sample + FromSample<f32>>(&mut self, data: &mut [T]) {
         for i in 0..data.len() / 2 {
-            data[2 * i] = self.audio[0][self.loc].to_sample();
-            data[2 * i + 1] = self.audio[1][self.loc].to_sample();
-            self.loc = (self.loc + 1) % self.audio[0].len();
+            let [l, r] = self.next_frame().unwrap_or([0.0; 2]);
+            data[2 * i] = l.to_sample();
+            data[2 * i + 1] = r.to_sample();
         }
     }
 
     #[inline]
     pub fn add_audio(&mut self, data: &mut [f32]) {
+        self.add_audio_with_input(data, None)
+    }
+
+    // like add_audio, with live audio (interleaved, the same length as data) added to what's read from the file.
+    // the input is heard even while the file is stopped
+    #[inline]
+    pub fn add_audio_with_input(&mut self, data: &mut [f32], input: Option<&[f32]>) {
         let buf_size = data.len() / 2;
-        if let Some((f1, f2)) = self.filter.as_mut() {
-            if self.old_decay != self.decay {
-                self.old_decay = self.decay;
-                f1.set_resonator_decays(4_f64.powf(self.decay) - 1.0);
-                f2.set_resonator_decays(4_f64.powf(self.decay) - 1.0);
-            }
-            if self.transpose != self.old_transpose {
-                self.old_transpose = self.transpose;
-                let trans_amt = 2_f64.powf(self.transpose);
-                let plan = self.plan.as_ref().unwrap();
-                let transpose_fn = |index: usize, res: &mut ConjPoleResonator| {
-                    res.set_arg(plan.resonators[index].0 * trans_amt);
-                };
-                f1.update_resonators(transpose_fn);
-                f2.update_resonators(transpose_fn);
-            }
+        if self.filter.is_some() {
+            // the modulation for this block, read by process_filter
+            let offsets = if self.params.modulation.is_active() {
+                self.modulation.advance(&self.params.modulation, buf_size, self.sample_rate)
+            } else {
+                Default::default()
+            };
             let mut audio1 = Vec::with_capacity(buf_size);
             let mut audio2 = Vec::with_capacity(buf_size);
+            // position of every frame, for automation and drawing the resonated output
+            let mut locs = Vec::with_capacity(buf_size);
+            // ramp the input gain over the buffer to avoid clicks
+            let prev_gain = self.old_input_gain;
+            self.old_input_gain = self.input_gain;
+            for i in 0..buf_size {
+                let gain = prev_gain + (self.input_gain - prev_gain) * (i as f64 / buf_size as f64);
+                locs.push(self.loc);
+                let [l, r] = self.next_frame_with_input(input, i);
+                audio1.push(l as f64 * gain);
+                audio2.push(r as f64 * gain);
+            }
+
             let mut chan1 = vec![0.0; buf_size];
             let mut chan2 = vec![0.0; buf_size];
-            for _ in 0..buf_size {
-                audio1.push(self.audio[0][self.loc] as f64);
-                audio2.push(self.audio[1][self.loc] as f64);
-                self.loc = (self.loc + 1) % self.audio[0].len();
+            let mut volumes = vec![self.params.volume + offsets.volume; buf_size];
+            if self.automation.is_playing() {
+                // split the buffer wherever the automation changes the filter
+                let mut start = 0;
+                for i in 0..buf_size {
+                    let current = (self.params.decay, self.params.volume, self.params.transpose, self.params.morph_pos);
+                    let (decay, volume, transpose, morph_pos) = self.automation.values_at(locs[i], current);
+                    if decay != self.params.decay || transpose != self.params.transpose || morph_pos != self.params.morph_pos {
+                        if i > start {
+                            self.process_keyed(&audio1[start..i], &audio2[start..i], &mut chan1[start..i], &mut chan2[start..i]);
+                        }
+                        start = i;
+                        self.params.decay = decay;
+                        self.params.transpose = transpose;
+                        self.params.morph_pos = morph_pos;
+                    }
+                    self.params.volume = volume;
+                    volumes[i] = volume + offsets.volume;
+                }
+                self.process_keyed(&audio1[start..], &audio2[start..], &mut chan1[start..], &mut chan2[start..]);
+            } else {
+                self.process_keyed(&audio1[..], &audio2[..], &mut chan1[..], &mut chan2[..]);
             }
-            f1.process_buf(&audio1[..], &mut chan1[..]);
-            f2.process_buf(&audio2[..], &mut chan2[..]);
 
             // internal limiting
             let mut max = 0.0;
             for i in 0..buf_size {
-                chan1[i] *= 10_f64.powf(self.volume * 0.1);
-                chan2[i] *= 10_f64.powf(self.volume * 0.1);
+                chan1[i] *= 10_f64.powf(volumes[i] * 0.1);
+                chan2[i] *= 10_f64.powf(volumes[i] * 0.1);
                 if chan1[i].abs() > max {
                     max = chan1[i].abs()
                 }
@@ -112,30 +327,285 @@ impl AudioState {
             self.limiter_scale = self.limiter_scale.max(-2.0);
             let new = self.limiter_scale as f32;
 
+            let mut added = Vec::with_capacity(2 * buf_size);
             for i in 0..buf_size {
-                data[2 * i] += chan1[i] as f32 / 2_f32.powf((new - prev) * (i as f32 / buf_size as f32) + prev + 2.0);
-                data[2 * i + 1] += chan2[i] as f32 / 2_f32.powf((new - prev) * (i as f32 / buf_size as f32) + prev + 2.0);
+                let out1 = chan1[i] as f32 / 2_f32.powf((new - prev) * (i as f32 / buf_size as f32) + prev + 2.0);
+                let out2 = chan2[i] as f32 / 2_f32.powf((new - prev) * (i as f32 / buf_size as f32) + prev + 2.0);
+                data[2 * i] += out1;
+                data[2 * i + 1] += out2;
+                added.push(out1);
+                added.push(out2);
+                let bucket = (locs[i] * OVERVIEW_BUCKETS / self.audio[0].len()).min(OVERVIEW_BUCKETS - 1);
+                // the bucket is reset when playback enters it so the overview shows the latest pass
+                if bucket != self.wet_bucket {
+                    self.wet_bucket = bucket;
+                    self.wet_overview[bucket] = 0.0;
+                }
+                self.wet_overview[bucket] = self.wet_overview[bucket].max(out1.abs()).max(out2.abs());
             }
+            self.meter.update(&added[..]);
         } else {
+            let mut added = Vec::with_capacity(2 * buf_size);
             for i in 0..buf_size {
-                data[2 * i] += self.audio[0][self.loc];
-                data[2 * i + 1] += self.audio[1][self.loc];
-                self.loc = (self.loc + 1) % self.audio[0].len();
+                let [l, r] = self.next_frame_with_input(input, i);
+                data[2 * i] += l;
+                data[2 * i + 1] += r;
+                added.push(l);
+                added.push(r);
             }
+            self.meter.update(&added[..]);
         }
         
     }
 
+    // runs the filter over one stretch of input, in blocks of SIDECHAIN_BLOCK with the decay
+    // following the envelope when the sidechain is on
+    #[inline]
+    fn process_keyed(&mut self, audio1: &[f64], audio2: &[f64], chan1: &mut [f64], chan2: &mut [f64]) {
+        if !self.params.sidechain.is_active() {
+            self.process_filter(audio1, audio2, chan1, chan2);
+            return;
+        }
+        let mut start = 0;
+        while start < audio1.len() {
+            let end = (start + SIDECHAIN_BLOCK).min(audio1.len());
+            self.sidechain.follow(&self.params.sidechain, [&audio1[start..end], &audio2[start..end]], self.sample_rate);
+            self.process_filter(&audio1[start..end], &audio2[start..end], &mut chan1[start..end], &mut chan2[start..end]);
+            start = end;
+        }
+    }
+
+    // applies decay, transpose and morph changes to the filter and runs it over one stretch of input
+    #[inline]
+    fn process_filter(&mut self, audio1: &[f64], audio2: &[f64], chan1: &mut [f64], chan2: &mut [f64]) {
+        let (mut decay, mut transpose) = (self.params.decay, self.params.transpose);
+        if self.params.modulation.is_active() {
+            let offsets = self.modulation.offsets();
+            decay = (decay + offsets.decay).max(0.0).min(1.0);
+            transpose += offsets.transpose;
+        }
+        if self.params.sidechain.is_active() {
+            decay = self.sidechain.modulate(&self.params.sidechain, decay);
+        }
+        let (f1, f2) = self.filter.as_mut().unwrap();
+        if self.old_decay != decay {
+            self.old_decay = decay;
+            let ring_time = 4_f64.powf(decay) - 1.0;
+            if self.params.shaping.shapes_decay() {
+                let [factors1, factors2] = &self.shaping_factors;
+                f1.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_decay(ring_time * factors1.get(index).map_or(1.0, |f| f.1));
+                });
+                f2.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_decay(ring_time * factors2.get(index).map_or(1.0, |f| f.1));
+                });
+            } else {
+                f1.set_resonator_decays(ring_time);
+                f2.set_resonator_decays(ring_time);
+            }
+        }
+        if transpose != self.old_transpose || self.params.morph_pos != self.old_morph_pos {
+            self.old_transpose = transpose;
+            self.old_morph_pos = self.params.morph_pos;
+            let trans_amt = 2_f64.powf(transpose);
+            if let Some((morph1, morph2)) = self.morph.as_ref() {
+                let pos = self.params.morph_pos;
+                let [factors1, factors2] = &self.shaping_factors;
+                f1.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_arg(morph1.arg_at(index, pos) * trans_amt);
+                    res.set_amp(morph1.amp_at(index, pos) * factors1.get(index).map_or(1.0, |f| f.0));
+                });
+                f2.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_arg(morph2.arg_at(index, pos) * trans_amt);
+                    res.set_amp(morph2.amp_at(index, pos) * factors2.get(index).map_or(1.0, |f| f.0));
+                });
+            } else {
+                let (plan1, plan2) = self.plan.as_ref().unwrap();
+                f1.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_arg(plan1.resonators[index].0 * trans_amt);
+                });
+                f2.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+                    res.set_arg(plan2.resonators[index].0 * trans_amt);
+                });
+            }
+        }
+        f1.process_buf(audio1, chan1);
+        f2.process_buf(audio2, chan2);
+    }
+
+    // sets the shaping of the track's resonators, see apply_shaping
+    pub fn set_shaping(&mut self, shaping: ShapingSettings) {
+        self.params.shaping = shaping;
+        self.apply_shaping();
+    }
+
+    // computes the shaping factors for the plan's frequencies and sets the gains, the ring times
+    // follow with the decay. it allocates, so it's called from the ui side whenever the plan or
+    // params.shaping changes and the audio thread only reads the factors
+    pub fn apply_shaping(&mut self) {
+        self.params.shaping.sort_curves();
+        let had_gain = self.old_shaping.as_ref().map_or(false, |s| s.shapes_gain());
+        self.old_shaping = Some(self.params.shaping.clone());
+        self.old_decay = f64::NAN;
+        let (plan1, plan2) = match self.plan.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+        self.shaping_factors = [
+            self.params.shaping.factors(&plan1.resonators, self.sample_rate),
+            self.params.shaping.factors(&plan2.resonators, self.sample_rate),
+        ];
+        if self.morph.is_some() {
+            // the morph sets the gains together with the args
+            self.old_morph_pos = f64::NAN;
+            return;
+        }
+        // the gains are left as built unless shaping changes them now or did before
+        if !self.params.shaping.shapes_gain() && !had_gain {
+            return;
+        }
+        // e.g. an undo whose arrays failed to build
+        let (f1, f2) = match self.filter.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        let [factors1, factors2] = &self.shaping_factors;
+        f1.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+            if let Some(r) = plan1.resonators.get(index) {
+                res.set_amp(r.1 * factors1.get(index).map_or(1.0, |f| f.0));
+            }
+        });
+        f2.update_resonators(|index: usize, res: &mut ConjPoleResonator| {
+            if let Some(r) = plan2.resonators.get(index) {
+                res.set_amp(r.1 * factors2.get(index).map_or(1.0, |f| f.0));
+            }
+        });
+    }
+
+    // writes the current value of a parameter into its automation lane when recording
+    #[inline]
+    pub fn record_automation(&mut self, param: AutomationParam) {
+        let value = match param {
+            AutomationParam::Decay => self.params.decay,
+            AutomationParam::Volume => self.params.volume,
+            AutomationParam::Transpose => self.params.transpose,
+            AutomationParam::Morph => self.params.morph_pos,
+        };
+        self.automation.record(param, self.loc, value);
+    }
+
     #[inline]
     pub fn set_loc(&mut self, v: f64) {
-        debug_assert!(v >= 0.0 && v <= 1.0);
+        // 1.0 is the end of the file, which is its last frame
+        let length = self.audio[0].len();
+        self.set_loc_samples((length as f64 * v.max(0.0).min(1.0)) as usize);
+    }
+
+    // playback position in samples
+    #[inline]
+    pub fn get_loc(&self) -> usize {
+        self.loc
+    }
+
+    #[inline]
+    pub fn set_loc_samples(&mut self, loc: usize) {
+        self.loc = loc.min(self.audio[0].len().saturating_sub(1));
+    }
 
+    // moves the playhead by a number of seconds, staying inside the file
+    #[inline]
+    pub fn seek_by(&mut self, seconds: f64) {
         let length = self.audio[0].len();
-        self.loc = (length as f64 * v) as usize;
+        let loc = self.loc as f64 + seconds * self.sample_rate;
+        self.loc = (loc.max(0.0) as usize).min(length.saturating_sub(1));
     }
 
     #[inline]
     pub fn get_progress(&self) -> f64 {
         self.loc as f64 / self.audio[0].len() as f64
     }
-}
\ No newline at end of file
+
+    // playback position in seconds
+    #[inline]
+    pub fn get_time(&self) -> f64 {
+        self.loc as f64 / self.sample_rate
+    }
+
+    // length of the track in seconds
+    #[inline]
+    pub fn get_duration(&self) -> f64 {
+        self.audio[0].len() as f64 / self.sample_rate
+    }
+
+    // starts or stops playback. starting sets the modulation envelopes off, stopping releases them
+    #[inline]
+    pub fn set_playing(&mut self, playing: bool) {
+        if playing && !self.playing {
+            self.modulation.trigger();
+        } else if !playing && self.playing {
+            self.modulation.release();
+        }
+        self.playing = playing;
+    }
+
+    // stop playback and go back to the start of the loop (or the file)
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.set_playing(false);
+        self.loc = if self.looping { self.loop_start } else { 0 };
+    }
+
+    // values between 0.0 and 1.0
+    #[inline]
+    pub fn set_loop_region(&mut self, start: f64, end: f64) {
+        debug_assert!(start >= 0.0 && end <= 1.0);
+
+        let length = self.audio[0].len();
+        let (start, end) = (start.min(end), start.max(end));
+        let start = (length as f64 * start) as usize;
+        let end = (length as f64 * end) as usize;
+        // keep room for the crossfade
+        self.loop_start = start.min(length.saturating_sub(2 * LOOP_CROSSFADE));
+        self.loop_end = end.max(self.loop_start + 2 * LOOP_CROSSFADE).min(length);
+    }
+
+    #[inline]
+    pub fn get_loop_region(&self) -> (f64, f64) {
+        let length = self.audio[0].len() as f64;
+        (self.loop_start as f64 / length, self.loop_end as f64 / length)
+    }
+}
+
+// formats seconds as mm:ss.ms
+pub fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0) as u64;
+    format!("{:02}:{:02}.{:03}", millis / 60_000, (millis / 1000) % 60, millis % 1000)
+}
+
+// the decay (0.0 to 1.0) that gives a ring time in seconds, the inverse of 4^decay - 1 in process_filter
+#[inline]
+pub fn decay_for_ring_time(seconds: f64) -> f64 {
+    (seconds + 1.0).log(4.0).max(0.0).min(1.0)
+}
+
+// (min, max, rms) of the mono mix of `audio` split into `buckets` columns
+fn compute_overview(audio: &[Vec<f32>; 2], buckets: usize) -> Vec<(f32, f32, f32)> {
+    let length = audio[0].len();
+    let mut out = Vec::with_capacity(buckets);
+    for b in 0..buckets {
+        let start = b * length / buckets;
+        let end = ((b + 1) * length / buckets).max(start + 1).min(length);
+        let mut min = 0.0_f32;
+        let mut max = 0.0_f32;
+        let mut sum_sq = 0.0;
+        for i in start..end {
+            let v = (audio[0][i] + audio[1][i]) / 2.0;
+            min = min.min(v);
+            max = max.max(v);
+            sum_sq += v * v;
+        }
+        let rms = (sum_sq / (end.saturating_sub(start)).max(1) as f32).sqrt();
+        out.push((min, max, rms));
+    }
+    out
+}
@@ -3,18 +3,38 @@ use cpal::{Sample, FromSample};
 use std::error::Error;
 use parking_lot::Mutex;
 use std::sync::Arc;
-use crate::AudioState;
+use crate::state::AudioState;
+use crate::level::LevelMeter;
+use crate::recorder::RecordTap;
 use lazy_static::lazy_static;
+use rtrb::{RingBuffer, Producer, Consumer};
+
+// about a second of audio for the output analyzer
+pub(crate) const ANALYZER_BUFFER_SIZE: usize = 1 << 16;
 
 lazy_static!{
     static ref AUDIO_STATE: Mutex<Option<Arc<Mutex<AudioState>>>> = Mutex::new(None);
     static ref R_AUDIO_STATE: Mutex<Option<Arc<Mutex<AudioState>>>> = Mutex::new(None);
+    static ref RECORD_TAP: Mutex<Option<RecordTap>> = Mutex::new(None);
+}
+
+// what the ui needs to observe the output of the stream
+pub struct StreamTaps {
+    // mono mix of the output, see analyzer::OutputAnalyzer
+    pub analyzer: Consumer<f32>,
+    pub output_sample_rate: f64,
+    pub output_channels: u16,
+    pub master_meter: Arc<LevelMeter>,
 }
 
-pub fn prepare_cpal_stream(audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<AudioState>>) -> Result<cpal::Stream, Box<dyn Error>> {
+pub fn prepare_cpal_stream(audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<AudioState>>) -> Result<(cpal::Stream, StreamTaps), Box<dyn Error>> {
     *AUDIO_STATE.lock() = Some(audio);
     *R_AUDIO_STATE.lock() = Some(r_audio);
 
+    let (mut analyzer, analyzer_consumer) = RingBuffer::new(ANALYZER_BUFFER_SIZE);
+    let master_meter = Arc::new(LevelMeter::new());
+    let callback_meter = Arc::clone(&master_meter);
+
     let host = cpal::default_host();
     let device = host.default_output_device().ok_or("No output device available")?;
 
@@ -23,11 +43,13 @@ pub fn prepare_cpal_stream(audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<Aud
     let supported_config = supported_configs_range.next()
         .ok_or("No supported config found")?
         .with_max_sample_rate();
+    let output_sample_rate = supported_config.sample_rate().0 as f64;
+    let output_channels = supported_config.channels();
 
     let stream = device.build_output_stream(
         &supported_config.into(),
         move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-            write_audio(data)
+            write_audio(data, &mut analyzer, &callback_meter)
         },
         move |err| {
             // react to errors here.
@@ -36,11 +58,22 @@ pub fn prepare_cpal_stream(audio: Arc<Mutex<AudioState>>, r_audio: Arc<Mutex<Aud
     ).map_err(|e| format!("Error while building output stream: {}", e))?;
     stream.play().map_err(|e| format!("Failed to play stream: {}", e))?;
 
-    Ok(stream)
+    let taps = StreamTaps {
+        analyzer: analyzer_consumer,
+        output_sample_rate,
+        output_channels,
+        master_meter,
+    };
+    Ok((stream, taps))
+}
+
+// the output is copied to the tap until it's replaced or removed, see recorder::Recorder
+pub fn set_record_tap(tap: Option<RecordTap>) {
+    *RECORD_TAP.lock() = tap;
 }
 
 #[inline]
-fn write_audio(data: &mut [f32]) {
+fn write_audio(data: &mut [f32], analyzer: &mut Producer<f32>, master_meter: &LevelMeter) {
     for sample in data.iter_mut() {
         *sample = Sample::EQUILIBRIUM;
     }
@@ -49,6 +82,8 @@ fn write_audio(data: &mut [f32]) {
     let mut audio = state.as_ref().unwrap().lock();
     if audio.playing {
         audio.add_audio(data);
+    } else {
+        audio.meter.clear();
     }
     std::mem::drop(audio);
     std::mem::drop(state);
@@ -57,11 +92,30 @@ fn write_audio(data: &mut [f32]) {
     let mut audio = state.as_ref().unwrap().lock();
     if audio.playing {
         audio.add_audio(data);
+    } else {
+        audio.meter.clear();
     }
     std::mem::drop(audio);
     std::mem::drop(state);
     
-    for v in data {
+    // meter before clamping so clipping is reported
+    master_meter.update(data);
+    for frame in data.chunks(2) {
+        // drop samples when the ui isn't keeping up
+        let _ = analyzer.push((frame[0] + frame[frame.len() - 1]) / 2.0);
+    }
+
+    for v in data.iter_mut() {
         *v = v.max(-1.0).min(1.0);
     }
-}
\ No newline at end of file
+
+    write_record_tap(data);
+}
+
+// copies the clamped output to the recorder, if one is recording. also used by the jack backend
+#[inline]
+pub(crate) fn write_record_tap(data: &[f32]) {
+    if let Some(tap) = RECORD_TAP.lock().as_mut() {
+        tap.write(data);
+    }
+}
@@ -0,0 +1,46 @@
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use druid::TimerToken;
+use std::time::Duration;
+use crate::AppState;
+use crate::state::format_time;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+// keeps the transport fields of AppState in sync with the audio thread,
+// which can stop a track on its own when a one-shot reaches the end
+pub struct TransportController {
+    timer: TimerToken,
+}
+
+impl TransportController {
+    pub fn new() -> Self {
+        Self {
+            timer: TimerToken::INVALID,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for TransportController {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        match event {
+            Event::WindowConnected => {
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+            },
+            Event::Timer(token) if *token == self.timer => {
+                let audio = data.audio_state.lock();
+                data.playing = audio.playing;
+                data.time = format_time(audio.get_time());
+                std::mem::drop(audio);
+                let r_audio = data.r_audio_state.lock();
+                data.r_playing = r_audio.playing;
+                data.r_time = format_time(r_audio.get_time());
+                std::mem::drop(r_audio);
+                self.timer = ctx.request_timer(REFRESH_INTERVAL);
+                return;
+            },
+            _ => {},
+        }
+        child.event(ctx, event, data, env)
+    }
+}
@@ -0,0 +1,425 @@
+// renders synthetic signals through AudioState::add_audio and compares the output with the buffers
+// in tests/golden. run with UPDATE_GOLDEN=1 to write the goldens after an intended change or for a
+// new test, a missing golden fails otherwise
+use resonator_builder::scaled_builder::ScaledResonatorPlan;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use capstone_audio_demo::modulation::{LfoShape, ModRoute, ModSource, ModTarget};
+use capstone_audio_demo::shaping::{PeakOverride, ShapingSettings};
+use capstone_audio_demo::sidechain::{SidechainKey, SidechainSettings};
+use capstone_audio_demo::state::AudioState;
+
+const SAMPLE_RATE: f64 = 48000.0;
+// frames per call to add_audio, about what the cpal callback gets
+const BLOCK_FRAMES: usize = 512;
+// largest difference allowed between a rendered sample and its golden
+const TOLERANCE: f32 = 1e-4;
+const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.wav", name))
+}
+
+fn write_golden(path: &PathBuf, out: &[f32]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for v in out {
+        writer.write_sample(*v).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+// compares interleaved stereo output with tests/golden/<name>.wav
+fn check_golden(name: &str, out: &[f32]) {
+    assert!(out.iter().all(|v| v.is_finite()), "{}: output has non-finite samples", name);
+    let path = golden_path(name);
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        write_golden(&path, out);
+        return;
+    }
+    assert!(path.exists(), "{}: no golden at {}, run with {}=1 to write it", name, path.display(), UPDATE_VAR);
+    let golden = hound::WavReader::open(&path)
+        .unwrap()
+        .into_samples::<f32>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(golden.len(), out.len(), "{}: golden has {} samples, rendered {}", name, golden.len(), out.len());
+    let (index, diff) = out
+        .iter()
+        .zip(golden.iter())
+        .map(|(a, b)| (a - b).abs())
+        .enumerate()
+        .fold((0, 0.0_f32), |m, (i, d)| if d > m.1 { (i, d) } else { m });
+    assert!(
+        diff <= TOLERANCE,
+        "{}: frame {} channel {} is off by {} (rendered {}, golden {})",
+        name, index / 2, index % 2, diff, out[index], golden[index],
+    );
+}
+
+fn impulse(frames: usize) -> Vec<f32> {
+    let mut v = vec![0.0; frames];
+    v[0] = 1.0;
+    v
+}
+
+fn sine(freq: f64, amp: f64, frames: usize) -> Vec<f32> {
+    (0..frames).map(|i| (amp * (2.0 * PI * freq * i as f64 / SAMPLE_RATE).sin()) as f32).collect()
+}
+
+// white noise from a fixed seed so every run gets the same signal
+fn noise(seed: u32, amp: f32, frames: usize) -> Vec<f32> {
+    let mut x = seed.max(1);
+    (0..frames)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            amp * (x as f32 / u32::MAX as f32 * 2.0 - 1.0)
+        })
+        .collect()
+}
+
+fn rms(samples: &[f32]) -> f64 {
+    (samples.iter().map(|v| (*v as f64).powi(2)).sum::<f64>() / samples.len().max(1) as f64).sqrt()
+}
+
+// a plan of (freq in hz, amp) resonators
+fn plan(resonators: &[(f64, f64)]) -> ScaledResonatorPlan {
+    let mut plan = ScaledResonatorPlan::empty();
+    plan.resonators = resonators.iter().map(|(f, a)| (f / SAMPLE_RATE * 2.0 * PI, *a)).collect();
+    plan
+}
+
+fn track(left: Vec<f32>, right: Vec<f32>) -> AudioState {
+    let mut audio_state = AudioState::from_samples("golden".to_string(), [left, right], SAMPLE_RATE);
+    audio_state.set_playing(true);
+    audio_state
+}
+
+// builds the filter the way BUILD RESONATOR does
+fn with_filter(mut audio_state: AudioState, plan1: ScaledResonatorPlan, plan2: ScaledResonatorPlan, decay: f64) -> AudioState {
+    audio_state.set_plan((plan1, plan2), None).unwrap();
+    audio_state.params.decay = decay;
+    audio_state.old_decay = f64::NAN;
+    audio_state.old_transpose = f64::NAN;
+    audio_state
+}
+
+// calls add_audio once per block while the track is playing, like the stream callback, and returns the interleaved output
+fn render(audio_state: &mut AudioState, blocks: usize) -> Vec<f32> {
+    let mut out = Vec::with_capacity(2 * BLOCK_FRAMES * blocks);
+    for _ in 0..blocks {
+        let mut block = vec![0.0_f32; 2 * BLOCK_FRAMES];
+        if audio_state.playing {
+            audio_state.add_audio(&mut block);
+        } else {
+            audio_state.meter.clear();
+        }
+        out.extend_from_slice(&block);
+    }
+    out
+}
+
+#[test]
+fn impulse_response() {
+    let frames = 16 * BLOCK_FRAMES;
+    let plan1 = plan(&[(220.0, 1.0), (440.0, 0.5), (1375.0, 0.25)]);
+    let plan2 = plan(&[(330.0, 1.0), (990.0, 0.3)]);
+    let mut audio_state = with_filter(track(impulse(frames), impulse(frames)), plan1, plan2, 0.5);
+    let out = render(&mut audio_state, 16);
+    check_golden("impulse_response", &out);
+}
+
+#[test]
+fn limiter() {
+    // a loud sine right on a resonator with the volume all the way up drives the limiter hard
+    let frames = 32 * BLOCK_FRAMES;
+    let plan1 = plan(&[(440.0, 1.0)]);
+    let mut audio_state = with_filter(track(sine(440.0, 1.0, frames), sine(440.0, 1.0, frames)), plan1.clone(), plan1, 1.0);
+    audio_state.params.volume = 6.0;
+    let out = render(&mut audio_state, 32);
+    assert!(audio_state.limiter_scale > 0.0, "limiter didn't engage, scale {}", audio_state.limiter_scale);
+    check_golden("limiter", &out);
+
+    // and lets go again once the input stops and the ring dies down
+    audio_state.input_gain = 0.0;
+    audio_state.params.decay = 0.1;
+    let quiet = render(&mut audio_state, 128);
+    assert!(audio_state.limiter_scale < 0.0, "limiter didn't release, scale {}", audio_state.limiter_scale);
+    check_golden("limiter_release", &quiet);
+}
+
+#[test]
+fn decay_change() {
+    let frames = 24 * BLOCK_FRAMES;
+    let plan1 = plan(&[(261.63, 1.0), (523.25, 0.5), (784.88, 0.33)]);
+    let plan2 = plan(&[(329.63, 1.0), (659.26, 0.5)]);
+    let mut burst = noise(1, 0.5, BLOCK_FRAMES);
+    burst.resize(frames, 0.0);
+    let mut audio_state = with_filter(track(burst.clone(), burst), plan1, plan2, 0.8);
+    let mut out = render(&mut audio_state, 8);
+    // shorten the ring while it's sounding, then lengthen it again
+    audio_state.params.decay = 0.2;
+    out.extend(render(&mut audio_state, 8));
+    audio_state.params.decay = 0.9;
+    out.extend(render(&mut audio_state, 8));
+    check_golden("decay_change", &out);
+}
+
+// how far the ring of a burst in the first block after lead blocks of silence has died down, as the
+// rms of blocks 14 to 16 after the burst over that of blocks 2 to 4
+fn tail_ratio(out: &[f32], lead: usize) -> f64 {
+    let window = |from: usize| rms(&out[2 * (lead + from) * BLOCK_FRAMES..2 * (lead + from + 2) * BLOCK_FRAMES]);
+    window(14) / window(2)
+}
+
+#[test]
+fn sidechain_decay() {
+    // a burst after enough silence for the limiter to settle at its floor, so the gain is the same
+    // throughout and the tails can be compared
+    let lead = 12;
+    let blocks = lead + 20;
+    let plan1 = plan(&[(440.0, 1.0)]);
+    let burst_track = |amp: f32, invert: bool| {
+        let mut input = vec![0.0; lead * BLOCK_FRAMES];
+        input.extend(noise(11, amp, BLOCK_FRAMES));
+        input.resize(blocks * BLOCK_FRAMES, 0.0);
+        let mut audio_state = with_filter(track(input.clone(), input), plan1.clone(), plan1.clone(), 0.7);
+        audio_state.params.volume = -40.0;
+        audio_state.params.sidechain = SidechainSettings {
+            key: SidechainKey::Input,
+            attack: 0.005,
+            // the envelope stays up through the part of the tail that's measured
+            release: 1.0,
+            depth: 0.3,
+            invert,
+        };
+        let mut out = render(&mut audio_state, lead);
+        for _ in lead..blocks {
+            out.extend(render(&mut audio_state, 1));
+            assert_eq!(audio_state.limiter_scale, -2.0, "the limiter moved, the tails can't be compared");
+        }
+        out
+    };
+
+    // the loud burst damps its own tail
+    let loud = burst_track(0.8, false);
+    let quiet = burst_track(0.01, false);
+    let (loud_ratio, quiet_ratio) = (tail_ratio(&loud, lead), tail_ratio(&quiet, lead));
+    assert!(loud_ratio < quiet_ratio, "loud tail fell to {}, quiet tail to {}", loud_ratio, quiet_ratio);
+
+    // inverted, the loud burst rings longer instead
+    let loud_inverted = burst_track(0.8, true);
+    let quiet_inverted = burst_track(0.01, true);
+    let (loud_ratio, quiet_ratio) = (tail_ratio(&loud_inverted, lead), tail_ratio(&quiet_inverted, lead));
+    assert!(loud_ratio > quiet_ratio, "inverted, loud tail fell to {}, quiet tail to {}", loud_ratio, quiet_ratio);
+
+    let out = [loud, quiet, loud_inverted, quiet_inverted].concat();
+    check_golden("sidechain_decay", &out);
+}
+
+#[test]
+fn modulation_matrix() {
+    // long enough for a whole cycle of the synced lfo
+    let blocks = 48;
+    let plan1 = plan(&[(330.0, 1.0), (495.0, 0.5)]);
+    let input = noise(5, 0.2, blocks * BLOCK_FRAMES);
+    let mut audio_state = with_filter(track(input.clone(), input), plan1.clone(), plan1, 0.6);
+    let settings = &mut audio_state.params.modulation;
+    // a synced triangle on transpose, a random lfo on decay and an envelope swelling the volume
+    settings.tempo = 150.0;
+    settings.lfos[0].shape = LfoShape::Triangle;
+    settings.lfos[0].sync = Some(1.0);
+    settings.lfos[1].shape = LfoShape::Random;
+    settings.lfos[1].rate = 8.0;
+    settings.envelopes[0].attack = 0.1;
+    settings.envelopes[0].decay = 0.1;
+    settings.envelopes[0].sustain = 0.3;
+    settings.envelopes[0].release = 0.05;
+    settings.routes = vec![
+        ModRoute { source: ModSource::Lfo(0), target: ModTarget::Transpose, depth: 0.1 },
+        ModRoute { source: ModSource::Lfo(1), target: ModTarget::Decay, depth: 0.2 },
+        ModRoute { source: ModSource::Envelope(0), target: ModTarget::Volume, depth: 12.0 },
+    ];
+    audio_state.params.volume = -12.0;
+    let mut out = Vec::new();
+    // the offsets of every block
+    let mut offsets = Vec::new();
+    for _ in 0..blocks {
+        out.extend(render(&mut audio_state, 1));
+        offsets.push(audio_state.modulation.offsets());
+    }
+
+    // the envelope goes all the way up and settles at the sustain level
+    let peak = offsets.iter().fold(0.0_f64, |m, o| m.max(o.volume));
+    assert!((peak - 12.0).abs() < 1e-9, "envelope peaked at {}", peak);
+    let sustain = offsets.last().unwrap().volume;
+    assert!((sustain - 0.3 * 12.0).abs() < 1e-9, "envelope held at {}", sustain);
+
+    // the triangle starts at zero rising, so it comes back up through zero after one cycle
+    let block_time = BLOCK_FRAMES as f64 / SAMPLE_RATE;
+    let period = 60.0 / audio_state.params.modulation.tempo * 1.0;
+    let crossing = (1..blocks)
+        .find(|&i| offsets[i - 1].transpose < 0.0 && offsets[i].transpose >= 0.0)
+        .expect("the lfo didn't complete a cycle");
+    let crossing_time = crossing as f64 * block_time;
+    assert!((crossing_time - period).abs() <= block_time, "lfo cycle took {} s, expected {} s", crossing_time, period);
+
+    // stopping releases the envelope, the input keeps the resonators going through the jack path
+    audio_state.set_playing(false);
+    let mut released = Vec::new();
+    for _ in 0..8 {
+        let mut block = vec![0.0_f32; 2 * BLOCK_FRAMES];
+        audio_state.add_audio_with_input(&mut block, Some(&noise(9, 0.2, 2 * BLOCK_FRAMES)));
+        out.extend_from_slice(&block);
+        released.push(audio_state.modulation.offsets().volume);
+    }
+    assert!(released.windows(2).all(|w| w[1] <= w[0]), "envelope rose after stopping: {:?}", released);
+    assert_eq!(*released.last().unwrap(), 0.0, "envelope didn't release: {:?}", released);
+    check_golden("modulation_matrix", &out);
+}
+
+// a single resonator at freq hz with the shaping, struck by an impulse after lead blocks of silence
+// so the limiter has settled at its floor and outputs can be compared
+fn struck(freq: f64, shaping: &ShapingSettings, lead: usize) -> Vec<f32> {
+    let blocks = lead + 20;
+    let mut input = vec![0.0; blocks * BLOCK_FRAMES];
+    input[lead * BLOCK_FRAMES] = 1.0;
+    let plan1 = plan(&[(freq, 1.0)]);
+    let mut audio_state = with_filter(track(input.clone(), input), plan1.clone(), plan1, 0.7);
+    audio_state.params.volume = -40.0;
+    audio_state.set_shaping(shaping.clone());
+    let mut out = render(&mut audio_state, lead);
+    for _ in lead..blocks {
+        out.extend(render(&mut audio_state, 1));
+        assert_eq!(audio_state.limiter_scale, -2.0, "the limiter moved, the outputs can't be compared");
+    }
+    out
+}
+
+#[test]
+fn resonator_shaping() {
+    let lead = 12;
+    let octaves = [220.0, 440.0, 880.0, 1760.0, 3520.0];
+    // high partials die faster like a bell
+    let tilted = ShapingSettings { decay_tilt: 0.6, ..Default::default() };
+    let ratios = octaves.iter().map(|f| tail_ratio(&struck(*f, &tilted, lead), lead)).collect::<Vec<_>>();
+    assert!(ratios.windows(2).all(|w| w[1] < w[0]), "higher octaves don't ring shorter: {:?}", ratios);
+
+    // the override holds the partial it's on
+    let held = ShapingSettings {
+        overrides: vec![PeakOverride { freq: 1760.0, gain: None, decay: Some(3.0) }],
+        ..tilted.clone()
+    };
+    let held_ratio = tail_ratio(&struck(1760.0, &held, lead), lead);
+    assert!(held_ratio > ratios[3], "the override rings to {}, without it {}", held_ratio, ratios[3]);
+
+    // the gains are the ones factors_at gives, against a shaping that sets the gains through the
+    // same path without changing them. the curve is out of order like it can be in a session file
+    let eq = ShapingSettings { gain_tilt: -1.5, gain_curve: vec![(3520.0, -12.0), (880.0, 0.0)], ..Default::default() };
+    let unity = ShapingSettings {
+        overrides: vec![PeakOverride { freq: 20.0, gain: Some(0.0), decay: None }],
+        ..Default::default()
+    };
+    let mut sorted = eq.clone();
+    sorted.sort_curves();
+    for freq in octaves {
+        let onset = |out: &[f32]| rms(&out[2 * lead * BLOCK_FRAMES..2 * (lead + 4) * BLOCK_FRAMES]);
+        let gain = onset(&struck(freq, &eq, lead)) / onset(&struck(freq, &unity, lead));
+        let expected = sorted.factors_at(freq).0;
+        assert!((gain / expected - 1.0).abs() < 1e-3, "{} hz has a gain of {}, expected {}", freq, gain, expected);
+    }
+
+    let frames = 24 * BLOCK_FRAMES;
+    let plan1 = plan(&octaves.map(|f| (f, 1.0)));
+    let mut audio_state = with_filter(track(impulse(frames), impulse(frames)), plan1.clone(), plan1, 0.7);
+    let mut out = render(&mut audio_state, 8);
+    audio_state.set_shaping(ShapingSettings { gain_curve: eq.gain_curve.clone(), ..held });
+    out.extend(render(&mut audio_state, 8));
+    // flat again, the gains go back to the plan's
+    audio_state.set_shaping(Default::default());
+    out.extend(render(&mut audio_state, 8));
+    check_golden("resonator_shaping", &out);
+}
+
+#[test]
+fn transpose_change() {
+    let frames = 24 * BLOCK_FRAMES;
+    let plan1 = plan(&[(440.0, 1.0), (880.0, 0.5)]);
+    let plan2 = plan(&[(440.0, 1.0), (1320.0, 0.25)]);
+    let input = noise(7, 0.2, frames);
+    let mut audio_state = with_filter(track(input.clone(), input), plan1, plan2, 0.6);
+    let mut out = render(&mut audio_state, 8);
+    // up an octave, then down a fifth below the original
+    audio_state.params.transpose = 1.0;
+    out.extend(render(&mut audio_state, 8));
+    audio_state.params.transpose = -7.0 / 12.0;
+    out.extend(render(&mut audio_state, 8));
+    check_golden("transpose_change", &out);
+}
+
+#[test]
+fn loop_wraparound() {
+    // a ramp makes the crossfade at the seam and the jump back easy to see in the golden
+    let frames = 8 * BLOCK_FRAMES;
+    let ramp = (0..frames).map(|i| i as f32 / frames as f32).collect::<Vec<_>>();
+    let mut audio_state = track(ramp.clone(), ramp.iter().map(|v| -v).collect());
+    audio_state.set_loop_region(0.25, 0.75);
+    audio_state.set_loc(0.7);
+    let out = render(&mut audio_state, 6);
+    let (start, end) = audio_state.get_loop_region();
+    let loc = audio_state.get_loc() as f64 / frames as f64;
+    assert!(loc >= start && loc < end, "playhead {} left the loop {}-{}", loc, start, end);
+    check_golden("loop_wraparound", &out);
+}
+
+#[test]
+fn seek_and_loop_with_filter() {
+    let frames = 16 * BLOCK_FRAMES;
+    let plan1 = plan(&[(300.0, 1.0), (750.0, 0.4)]);
+    let input = sine(300.0, 0.3, frames);
+    let mut audio_state = with_filter(track(input.clone(), input), plan1.clone(), plan1, 0.4);
+    audio_state.set_loop_region(0.5, 1.0);
+    let mut out = render(&mut audio_state, 4);
+    // seeking past the end stays inside the file, the next block wraps back to the loop start
+    audio_state.seek_by(1.0);
+    assert_eq!(audio_state.get_loc(), frames - 1);
+    out.extend(render(&mut audio_state, 4));
+    audio_state.seek_by(-1.0);
+    assert_eq!(audio_state.get_loc(), 0);
+    out.extend(render(&mut audio_state, 4));
+    check_golden("seek_and_loop_with_filter", &out);
+}
+
+#[test]
+fn one_shot_stops() {
+    let frames = 4 * BLOCK_FRAMES + BLOCK_FRAMES / 2;
+    let input = noise(3, 0.5, frames);
+    let mut audio_state = track(input.clone(), input);
+    audio_state.looping = false;
+    let out = render(&mut audio_state, 5);
+    assert!(!audio_state.playing);
+    assert_eq!(audio_state.get_loc(), 0);
+    // everything after the end of the file is silence
+    assert!(out[2 * frames..].iter().all(|v| *v == 0.0));
+    check_golden("one_shot_stops", &out);
+}
+
+#[test]
+fn stopped_meter_is_silent() {
+    let input = noise(4, 0.5, 8 * BLOCK_FRAMES);
+    let mut audio_state = track(input.clone(), input);
+    render(&mut audio_state, 2);
+    assert!(audio_state.meter.rms() > 0.0);
+    // the other track's sidechain reads the meter, it must not hold the last block played
+    audio_state.set_playing(false);
+    render(&mut audio_state, 1);
+    assert_eq!(audio_state.meter.rms(), 0.0);
+}